@@ -2,7 +2,7 @@ use gamma::prelude::*;
 
 fn draw(gamma: &mut Gamma<()>, _state: &mut ()) {
     // At a minimum we need to call clear_screen in order to show a window
-    gamma.clear_screen(255, 255, 255);
+    gamma.clear_screen(Color::WHITE);
 }
 
 fn main() {
@@ -24,7 +24,7 @@ pub fn update(gamma: &mut Gamma<GameState>, state: &mut GameState) {
 // The draw function is called every frame after update has finished processing.
 pub fn draw(gamma: &mut Gamma<GameState>, _state: &mut GameState) {
     // Call clear screen at a minimum in order for the window to show.
-    gamma.clear_screen(255, 0, 0);
+    gamma.clear_screen(Color::RED);
 }
 
 // The init function is used to create your game state. In more complex scenarios,
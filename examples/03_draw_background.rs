@@ -22,7 +22,7 @@ pub fn update(gamma: &mut Gamma<GameState>, state: &mut GameState) {
 
 // The draw function is called every frame after update has finished processing.
 pub fn draw(gamma: &mut Gamma<GameState>, state: &mut GameState) {
-    gamma.clear_screen(255, 0, 0);
+    gamma.clear_screen(Color::RED);
     gamma.draw_texture(&state.background, 0.0, 0.0, Flip::None);
     gamma.draw_texture(&state.player, state.player_x, state.player_y, Flip::None);
 }
@@ -34,7 +34,7 @@ pub fn draw(gamma: &mut Gamma<GameState>, state: &mut GameState) {
         Facing::Right => Flip::Horizontal,
     };
 
-    gamma.clear_screen(255, 0, 0);
+    gamma.clear_screen(Color::RED);
     gamma.draw_texture(&state.background, 0.0, 0.0, Flip::None);
     gamma.draw_texture(&state.player, state.player_x, state.player_y, flip);
 }
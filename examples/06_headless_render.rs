@@ -0,0 +1,59 @@
+use gamma::prelude::*;
+
+// Headless rendering draws into an offscreen texture instead of a window, which makes it
+// possible to compare what the engine draws against a known good image in CI.
+pub struct GameState {
+    pub player_x: f32,
+    pub background: Texture,
+    pub player: Texture,
+}
+
+// The update function still runs every frame, and time always advances by exactly 1/60th of a second.
+pub fn update(gamma: &mut Gamma<GameState>, state: &mut GameState) {
+    state.player_x += 100.0 * gamma.delta_time().as_secs_f32();
+}
+
+pub fn draw(gamma: &mut Gamma<GameState>, state: &mut GameState) {
+    gamma.clear_screen(Color::RED);
+    gamma.draw_texture(&state.background, 0.0, 0.0, Flip::None);
+    gamma.draw_texture(&state.player, state.player_x, 540.0, Flip::None);
+}
+
+pub fn init(gamma: &mut Gamma<GameState>) -> GameState {
+    let background = gamma
+        .load_texture_from_bytes(include_bytes!("../assets/graphics/background.png"))
+        .unwrap();
+
+    let player = gamma
+        .load_texture_from_bytes(include_bytes!("../assets/graphics/player.png"))
+        .unwrap();
+
+    GameState {
+        player_x: 0.0,
+        background,
+        player,
+    }
+}
+
+pub fn main() {
+    // Run 60 frames (one second of game time) and get back the pixels of the last frame.
+    let result = GammaBuilder::default()
+        .with_size(1920, 1080)
+        .on_init(init)
+        .on_update(update)
+        .on_draw(draw)
+        .run_headless(60);
+
+    match result {
+        Ok(image) => {
+            // The player should have moved 100 pixels to the right.
+            image.save("headless_frame.png").unwrap();
+            println!(
+                "Saved headless_frame.png ({}x{})",
+                image.width(),
+                image.height()
+            );
+        }
+        Err(msg) => println!("Unexpected error occurred: {msg}"),
+    }
+}
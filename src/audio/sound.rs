@@ -2,7 +2,7 @@ use std::io::Cursor;
 
 use rodio::{Decoder, Sink};
 
-use crate::gamma::Gamma;
+use crate::{error::GammaError, gamma::Gamma};
 
 pub struct Sound {
     data: Vec<u8>,
@@ -18,17 +18,28 @@ impl Sound {
 }
 
 impl<S> Gamma<S> {
-    pub fn load_sound_from_bytes(&self, bytes: &[u8]) -> Result<Sound, String> {
+    pub fn load_sound_from_bytes(&self, bytes: &[u8]) -> Result<Sound, GammaError> {
         let bytes = bytes.to_vec();
+
+        // Decode once up front so a bad file is reported here instead of when it is played.
+        Decoder::new(Cursor::new(bytes.clone()))
+            .map_err(|e| GammaError::Decode(format!("Failed to decode sound: {}", e)))?;
+
         Ok(Sound {
             data: bytes,
             volume: 1.0,
         })
     }
 
+    /// Play a sound. Nothing is played when running headless.
     pub fn play_sound(&mut self, sound: &Sound) {
-        let mixer = self.stream_handle.mixer();
-        let source = Decoder::new(Cursor::new(sound.data.clone())).expect("failed to decode audio");
+        let Some(stream_handle) = self.stream_handle.as_ref() else {
+            return;
+        };
+        let mixer = stream_handle.mixer();
+        let Ok(source) = Decoder::new(Cursor::new(sound.data.clone())) else {
+            return;
+        };
         let sink = Sink::connect_new(mixer);
         sink.set_volume(sound.volume);
         sink.append(source);
@@ -1,11 +1,21 @@
 use std::time::Instant;
 
+use image::RgbaImage;
+use rodio::OutputStreamBuilder;
+
+use wgpu::{Backends, Features, Limits, PowerPreference};
 use winit::{
-    dpi::LogicalSize,
+    dpi::{LogicalPosition, LogicalSize},
     event_loop::{ControlFlow, EventLoop},
+    keyboard::KeyCode,
 };
 
-use crate::{engine::GammaRuntime, gamma::Gamma};
+use crate::{
+    engine::{GammaRuntime, headless::run_headless},
+    error::GammaError,
+    gamma::Gamma,
+    rendering::{PresentMode, adapter::AdapterOptions},
+};
 
 pub type UpdateFn<S> = fn(&mut Gamma<S>, &mut S);
 pub type DrawFn<S> = fn(&mut Gamma<S>, &mut S);
@@ -22,8 +32,21 @@ pub struct GammaBuilder<S> {
     pub(crate) logical_size: Option<LogicalSize<f64>>,
     pub(crate) resizable: bool,
     pub(crate) vsync: bool,
+    pub(crate) present_mode: Option<PresentMode>,
+    pub(crate) max_frame_latency: u32,
+    pub(crate) adapter_options: AdapterOptions,
     pub(crate) fullscreen: bool,
+    pub(crate) transparent: bool,
+    pub(crate) decorations: bool,
+    pub(crate) always_on_top: bool,
+    pub(crate) icon: Option<Vec<u8>>,
+    pub(crate) position: Option<LogicalPosition<f64>>,
+    pub(crate) min_size: Option<LogicalSize<f64>>,
+    pub(crate) max_size: Option<LogicalSize<f64>>,
     pub(crate) close_on_escape: bool,
+    pub(crate) show_stats_overlay: bool,
+    pub(crate) debug_key: Option<KeyCode>,
+    pub(crate) msaa: u32,
 }
 
 impl<S> Default for GammaBuilder<S> {
@@ -39,8 +62,21 @@ impl<S> Default for GammaBuilder<S> {
             logical_size: None,
             resizable: true,
             vsync: true,
+            present_mode: None,
+            max_frame_latency: 2,
+            adapter_options: AdapterOptions::default(),
             fullscreen: false,
+            transparent: false,
+            decorations: true,
+            always_on_top: false,
+            icon: None,
+            position: None,
+            min_size: None,
+            max_size: None,
             close_on_escape: false,
+            show_stats_overlay: false,
+            debug_key: None,
+            msaa: 1,
         }
     }
 }
@@ -66,16 +102,125 @@ impl<S> GammaBuilder<S> {
         self
     }
 
+    /// Choose exactly how frames are presented, overriding `with_vsync`.
+    pub fn with_present_mode(mut self, present_mode: PresentMode) -> Self {
+        self.present_mode = Some(present_mode);
+        self
+    }
+
+    /// The number of frames the GPU may queue up ahead of the display. Lower values reduce
+    /// input latency, higher values smooth out uneven frame times. Defaults to 2.
+    pub fn with_max_frame_latency(mut self, max_frame_latency: u32) -> Self {
+        self.max_frame_latency = max_frame_latency;
+        self
+    }
+
+    /// Prefer a fast discrete GPU or a power saving integrated GPU. Defaults to high performance.
+    pub fn with_power_preference(mut self, power_preference: PowerPreference) -> Self {
+        self.adapter_options.power_preference = power_preference;
+        self
+    }
+
+    /// Limit which graphics APIs may be used, such as `Backends::VULKAN` or `Backends::GL`.
+    pub fn with_backends(mut self, backends: Backends) -> Self {
+        self.adapter_options.backends = backends;
+        self
+    }
+
+    /// Only use a software adapter, for machines without a working GPU driver.
+    pub fn with_fallback_adapter(mut self, force_fallback_adapter: bool) -> Self {
+        self.adapter_options.force_fallback_adapter = force_fallback_adapter;
+        self
+    }
+
+    /// GPU features the game needs. Adapters without them are skipped.
+    pub fn with_required_features(mut self, features: Features) -> Self {
+        self.adapter_options.required_features = features;
+        self
+    }
+
+    /// GPU limits the game needs. Adapters that cannot meet them are skipped.
+    pub fn with_required_limits(mut self, limits: Limits) -> Self {
+        self.adapter_options.required_limits = limits;
+        self
+    }
+
     pub fn with_fullscreen(mut self, fullscreen: bool) -> Self {
         self.fullscreen = fullscreen;
         self
     }
 
+    /// Let the desktop show through wherever the frame is transparent, such as after clearing
+    /// with `Color::TRANSPARENT`. Not every platform supports transparent windows.
+    pub fn with_transparent(mut self, transparent: bool) -> Self {
+        self.transparent = transparent;
+        self
+    }
+
+    /// Show the title bar and borders. Defaults to true.
+    pub fn with_decorations(mut self, decorations: bool) -> Self {
+        self.decorations = decorations;
+        self
+    }
+
+    /// Keep the window above other windows.
+    pub fn with_always_on_top(mut self, always_on_top: bool) -> Self {
+        self.always_on_top = always_on_top;
+        self
+    }
+
+    /// The window icon, from the bytes of a PNG image such as one loaded with `include_bytes!`.
+    /// An image that cannot be decoded stops `run` with an error.
+    pub fn with_icon(mut self, png: &[u8]) -> Self {
+        self.icon = Some(png.to_vec());
+        self
+    }
+
+    /// Where the top left corner of the window is placed on the desktop.
+    pub fn with_position(mut self, x: i32, y: i32) -> Self {
+        self.position = Some(LogicalPosition::new(x.into(), y.into()));
+        self
+    }
+
+    /// The smallest size the window can be resized to.
+    pub fn with_min_size(mut self, width: u16, height: u16) -> Self {
+        self.min_size = Some(LogicalSize::new(width.into(), height.into()));
+        self
+    }
+
+    /// The largest size the window can be resized to.
+    pub fn with_max_size(mut self, width: u16, height: u16) -> Self {
+        self.max_size = Some(LogicalSize::new(width.into(), height.into()));
+        self
+    }
+
     pub fn with_close_on_escape(mut self, close_on_escape: bool) -> Self {
         self.close_on_escape = close_on_escape;
         self
     }
 
+    /// Show an overlay with the render statistics of the previous frame.
+    pub fn with_stats_overlay(mut self, show_stats_overlay: bool) -> Self {
+        self.show_stats_overlay = show_stats_overlay;
+        self
+    }
+
+    /// The key that turns drawing with `debug` on and off, such as `Some(KeyCode::F3)`.
+    /// Defaults to `None`, so players of a released game cannot turn it on.
+    pub fn with_debug_key(mut self, key: Option<KeyCode>) -> Self {
+        self.debug_key = key;
+        self
+    }
+
+    /// Smooth jagged edges with multisample anti-aliasing, using 2, 4 or 8 samples per pixel.
+    ///
+    /// The highest count up to `samples` that the adapter supports is used, or none at all.
+    /// Check the count in use with `msaa_samples`.
+    pub fn with_msaa(mut self, samples: u32) -> Self {
+        self.msaa = samples;
+        self
+    }
+
     pub fn on_init(mut self, init: InitFn<S>) -> Self {
         self.init_fn = Some(init);
         self
@@ -91,26 +236,20 @@ impl<S> GammaBuilder<S> {
         self
     }
 
-    pub fn run(self) -> Result<(), String> {
+    pub fn run(self) -> Result<(), GammaError> {
         if self.init_fn.is_none() {
-            eprintln!(
-                "Cannot call draw or update without init, please register game state with the `on_init` builder method"
-            );
-            std::process::exit(-1);
+            return Err(GammaError::MissingInit);
         }
 
-        let mut gamma_instance = Gamma::<S>::default();
-        gamma_instance.last_frame_time = Instant::now();
-        gamma_instance.init_fn = self.init_fn;
-        gamma_instance.logical_size = self.logical_size.unwrap_or(gamma_instance.logical_size);
-        gamma_instance.draw_fn = self.draw_fn.unwrap_or(|_, _| {});
-        gamma_instance.update_fn = self.update_fn.unwrap_or(|_, _| {});
-        gamma_instance.resizable = self.resizable;
-        gamma_instance.vsync = self.vsync;
-        gamma_instance.fullscreen = self.fullscreen;
-        gamma_instance.close_on_escape = self.close_on_escape;
+        let mut gamma_instance = self.into_gamma();
+        gamma_instance.stream_handle = Some(
+            OutputStreamBuilder::open_default_stream()
+                .map_err(|err| GammaError::Audio(format!("Failed to open audio output: {err}")))?,
+        );
 
-        let event_loop = EventLoop::new().expect("Error occurred starting the event loop");
+        let event_loop = EventLoop::new().map_err(|err| {
+            GammaError::Window(format!("Error occurred starting the event loop: {err}"))
+        })?;
         event_loop.set_control_flow(ControlFlow::Poll);
 
         gamma_instance.last_frame_time = Instant::now();
@@ -118,10 +257,53 @@ impl<S> GammaBuilder<S> {
         let mut runtime = GammaRuntime::<S> {
             context: gamma_instance,
             state: None,
+            error: None,
         };
 
         event_loop
             .run_app(&mut runtime)
-            .map_err(|err| err.to_string())
+            .map_err(|err| GammaError::Window(err.to_string()))?;
+
+        match runtime.error {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+
+    /// Run the game without a window for a fixed number of frames and return the last frame.
+    ///
+    /// Frames are drawn into an offscreen texture of the size given to `with_size`, preferring a
+    /// software adapter, and every frame advances time by exactly 1/60th of a second.
+    /// Audio is disabled. This is intended for automated tests of rendering output.
+    pub fn run_headless(self, frames: u32) -> Result<RgbaImage, GammaError> {
+        run_headless(self.into_gamma(), frames)
+    }
+
+    fn into_gamma(self) -> Gamma<S> {
+        let mut gamma_instance = Gamma::<S>::default();
+        gamma_instance.last_frame_time = Instant::now();
+        gamma_instance.init_fn = self.init_fn;
+        gamma_instance.title = self.title.unwrap_or(gamma_instance.title);
+        gamma_instance.logical_size = self.logical_size.unwrap_or(gamma_instance.logical_size);
+        gamma_instance.draw_fn = self.draw_fn.unwrap_or(|_, _| {});
+        gamma_instance.update_fn = self.update_fn.unwrap_or(|_, _| {});
+        gamma_instance.resizable = self.resizable;
+        gamma_instance.vsync = self.vsync;
+        gamma_instance.present_mode = self.present_mode;
+        gamma_instance.max_frame_latency = self.max_frame_latency;
+        gamma_instance.adapter_options = self.adapter_options;
+        gamma_instance.fullscreen = self.fullscreen;
+        gamma_instance.transparent = self.transparent;
+        gamma_instance.decorations = self.decorations;
+        gamma_instance.always_on_top = self.always_on_top;
+        gamma_instance.icon = self.icon;
+        gamma_instance.position = self.position;
+        gamma_instance.min_size = self.min_size;
+        gamma_instance.max_size = self.max_size;
+        gamma_instance.close_on_escape = self.close_on_escape;
+        gamma_instance.show_stats_overlay = self.show_stats_overlay;
+        gamma_instance.debug_key = self.debug_key;
+        gamma_instance.msaa = self.msaa;
+        gamma_instance
     }
 }
@@ -0,0 +1,52 @@
+use std::time::Duration;
+
+use image::RgbaImage;
+
+use crate::{
+    engine::GammaRuntime,
+    error::GammaError,
+    gamma::Gamma,
+    rendering::{headless::initialize_headless_rendering, readback::ReadbackBuffer},
+};
+
+/// Headless frames always advance by the same amount so runs are reproducible.
+const HEADLESS_FRAME_TIME: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+/// Run the game for a fixed number of frames without a window and return the final frame.
+pub(crate) fn run_headless<S>(mut context: Gamma<S>, frames: u32) -> Result<RgbaImage, GammaError> {
+    let width = context.logical_size.width as u32;
+    let height = context.logical_size.height as u32;
+    initialize_headless_rendering(&mut context, width, height)?;
+
+    let init = context.init_fn.ok_or(GammaError::MissingInit)?;
+
+    let mut runtime = GammaRuntime::<S> {
+        state: None,
+        error: None,
+        context,
+    };
+    runtime.state = Some(init(&mut runtime.context));
+
+    for _ in 0..frames {
+        runtime.step(HEADLESS_FRAME_TIME);
+    }
+    runtime.context.finish_recording();
+
+    let context = &runtime.context;
+    let (device, queue, target) = match (
+        context.device.as_ref(),
+        context.queue.as_ref(),
+        context.headless_target.as_ref(),
+    ) {
+        (Some(d), Some(q), Some(t)) => (d, q, t),
+        _ => return Err(GammaError::NotInitialized("Headless rendering")),
+    };
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Headless Readback Encoder"),
+    });
+    let readback = ReadbackBuffer::copy_from_texture(device, &mut encoder, target)?;
+    queue.submit(std::iter::once(encoder.finish()));
+    readback.map_blocking(device)?;
+    readback.to_image()
+}
@@ -1,7 +1,52 @@
+use std::collections::HashMap;
+
 use winit::keyboard::KeyCode;
 
 use crate::gamma::Gamma;
 
+/// The keys one player uses, bound to named actions such as "jump", so several players can
+/// share a keyboard.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Controller {
+    bindings: HashMap<String, Vec<KeyCode>>,
+}
+
+impl Controller {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Bind a key to an action. An action can have several keys and is pressed while any of them is.
+    pub fn with_binding(mut self, action: impl Into<String>, key: KeyCode) -> Self {
+        self.bindings.entry(action.into()).or_default().push(key);
+        self
+    }
+
+    /// "up", "down", "left" and "right" on W, S, A and D, and "action" on Space.
+    pub fn wasd() -> Self {
+        Self::new()
+            .with_binding("up", KeyCode::KeyW)
+            .with_binding("down", KeyCode::KeyS)
+            .with_binding("left", KeyCode::KeyA)
+            .with_binding("right", KeyCode::KeyD)
+            .with_binding("action", KeyCode::Space)
+    }
+
+    /// "up", "down", "left" and "right" on the arrow keys, and "action" on Enter.
+    pub fn arrow_keys() -> Self {
+        Self::new()
+            .with_binding("up", KeyCode::ArrowUp)
+            .with_binding("down", KeyCode::ArrowDown)
+            .with_binding("left", KeyCode::ArrowLeft)
+            .with_binding("right", KeyCode::ArrowRight)
+            .with_binding("action", KeyCode::Enter)
+    }
+
+    fn keys(&self, action: &str) -> &[KeyCode] {
+        self.bindings.get(action).map_or(&[], Vec::as_slice)
+    }
+}
+
 impl<S> Gamma<S> {
     pub fn is_key_pressed(&self, key: KeyCode) -> bool {
         self.pressed_keys.contains(&key)
@@ -14,4 +59,38 @@ impl<S> Gamma<S> {
     pub fn is_key_just_released(&self, key: KeyCode) -> bool {
         self.just_released_keys.contains(&key)
     }
+
+    /// Give a player their controller, replacing the one they had.
+    pub fn set_controller(&mut self, player: usize, controller: Controller) {
+        if self.controllers.len() <= player {
+            self.controllers
+                .resize_with(player + 1, Controller::default);
+        }
+        self.controllers[player] = controller;
+    }
+
+    /// Whether any key bound to an action on a player's controller is held down.
+    pub fn is_action_pressed(&self, player: usize, action: &str) -> bool {
+        self.action_keys(player, action)
+            .iter()
+            .any(|key| self.is_key_pressed(*key))
+    }
+
+    pub fn is_action_just_pressed(&self, player: usize, action: &str) -> bool {
+        self.action_keys(player, action)
+            .iter()
+            .any(|key| self.is_key_just_pressed(*key))
+    }
+
+    pub fn is_action_just_released(&self, player: usize, action: &str) -> bool {
+        self.action_keys(player, action)
+            .iter()
+            .any(|key| self.is_key_just_released(*key))
+    }
+
+    fn action_keys(&self, player: usize, action: &str) -> &[KeyCode] {
+        self.controllers
+            .get(player)
+            .map_or(&[], |controller| controller.keys(action))
+    }
 }
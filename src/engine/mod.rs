@@ -1,5 +1,7 @@
+pub(crate) mod headless;
 mod input;
 mod runtime;
 mod time;
 
+pub use input::Controller;
 pub(crate) use runtime::*;
@@ -1,4 +1,4 @@
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use winit::{
     application::ApplicationHandler,
@@ -8,15 +8,19 @@ use winit::{
     window::WindowId,
 };
 
-use crate::{gamma::Gamma, rendering::context::initialize_rendering};
+use crate::{error::GammaError, gamma::Gamma, rendering::context::initialize_rendering};
 
 pub struct GammaRuntime<S> {
     pub(crate) context: Gamma<S>,
     pub(crate) state: Option<S>,
+    /// The error that stopped the event loop early, returned from `run`.
+    pub(crate) error: Option<GammaError>,
 }
 
 impl<S> GammaRuntime<S> {
     pub(crate) fn shutdown(&mut self, event_loop: &ActiveEventLoop) {
+        self.context.finish_recording();
+
         self.context.surface = None;
         self.context.device = None;
         self.context.queue = None;
@@ -25,10 +29,52 @@ impl<S> GammaRuntime<S> {
         self.context.surface_config = None;
         self.context.window = None;
         self.context.texture_pipeline = None;
+        self.context.text_renderer = None;
+        self.context.sdf_text_pipeline = None;
+        self.context.sprite_effect_pipeline = None;
+        self.context.lighting = Default::default();
+        self.context.textures = Default::default();
+        self.context.mipmap_pipeline = None;
+        self.context.instance_pipeline = None;
+        self.context.mesh_pipeline = None;
+        self.context.headless_target = None;
+        self.context.stencil_texture = None;
+        self.context.msaa_texture = None;
+        self.context.extra_windows.clear();
+        self.context.window_requests.clear();
         self.state = None;
 
         event_loop.exit();
     }
+
+    /// Run a single frame: update, draw and present.
+    pub(crate) fn step(&mut self, delta: Duration) {
+        self.context.delta = delta;
+        self.context.elapsed += delta;
+
+        let update_fn = self.context.update_fn;
+        let draw_fn = self.context.draw_fn;
+
+        let state = self.state.as_mut().expect("state not found when expected");
+
+        // Call the user's update function to update the game state.
+        let update_start = Instant::now();
+        update_fn(&mut self.context, state);
+        self.context.frame_stats.update_time = update_start.elapsed();
+
+        // Call the user's draw function to prepare to draw to the window.
+        let draw_start = Instant::now();
+        draw_fn(&mut self.context, state);
+        self.context.draw_extra_windows(state);
+        self.context.frame_stats.draw_time = draw_start.elapsed();
+        self.context.frame_stats.frame_time = delta;
+
+        // Clear the just_pressed_keys and just_released_keys for the next frame
+        self.context.just_pressed_keys.clear();
+        self.context.just_released_keys.clear();
+
+        self.context.end_frame();
+    }
 }
 
 impl<S> ApplicationHandler for GammaRuntime<S> {
@@ -37,9 +83,17 @@ impl<S> ApplicationHandler for GammaRuntime<S> {
             return; // Already initialized
         }
 
-        initialize_rendering(&mut self.context, event_loop);
+        if let Err(error) = initialize_rendering(&mut self.context, event_loop) {
+            self.error = Some(error);
+            self.shutdown(event_loop);
+            return;
+        }
 
-        let init = self.context.init_fn.unwrap();
+        let Some(init) = self.context.init_fn else {
+            self.error = Some(GammaError::MissingInit);
+            self.shutdown(event_loop);
+            return;
+        };
         self.state = Some(init(&mut self.context));
 
         if let Some(window) = &self.context.window {
@@ -47,32 +101,36 @@ impl<S> ApplicationHandler for GammaRuntime<S> {
         }
     }
 
-    fn window_event(&mut self, event_loop: &ActiveEventLoop, _id: WindowId, event: WindowEvent) {
+    fn window_event(&mut self, event_loop: &ActiveEventLoop, id: WindowId, event: WindowEvent) {
+        let is_main_window = self
+            .context
+            .window
+            .as_ref()
+            .is_some_and(|window| window.id() == id);
+
         match event {
             WindowEvent::CloseRequested => {
-                self.shutdown(event_loop);
+                if is_main_window {
+                    self.shutdown(event_loop);
+                } else {
+                    self.context.close_extra_window(id);
+                }
             }
-            WindowEvent::RedrawRequested => {
-                self.context.delta = Instant::now() - self.context.last_frame_time;
-                self.context.last_frame_time = Instant::now();
-
-                let update_fn = self.context.update_fn;
-                let draw_fn = self.context.draw_fn;
-
-                let state = self.state.as_mut().expect("state not found when expected");
-
-                // Call the user's update function to update the game state.
-                update_fn(&mut self.context, state);
-
-                // Call the user's draw function to prepare to draw to the window.
-                draw_fn(&mut self.context, state);
-
-                // Clear the just_pressed_keys and just_released_keys for the next frame
-                self.context.just_pressed_keys.clear();
-                self.context.just_released_keys.clear();
-
-                if let Some(frame) = self.context.current_frame.take() {
-                    frame.texture.present();
+            WindowEvent::Resized(size) => {
+                self.context.resize_window(id, size.width, size.height);
+            }
+            // Every window is drawn when the main window redraws.
+            WindowEvent::RedrawRequested if is_main_window => {
+                let now = Instant::now();
+                let delta = now - self.context.last_frame_time;
+                self.context.last_frame_time = now;
+
+                self.step(delta);
+
+                self.context.open_requested_windows(event_loop);
+                if self.context.close_requested_windows() {
+                    self.shutdown(event_loop);
+                    return;
                 }
 
                 // Only request redraw if still running and window exists
@@ -89,6 +147,14 @@ impl<S> ApplicationHandler for GammaRuntime<S> {
                         self.shutdown(event_loop);
                     }
 
+                    if event.state.is_pressed()
+                        && !event.repeat
+                        && self.context.debug_key == Some(keycode)
+                    {
+                        let enabled = self.context.is_debug_draw_enabled();
+                        self.context.set_debug_draw(!enabled);
+                    }
+
                     if event.state.is_pressed() {
                         if self.context.pressed_keys.insert(keycode) {
                             self.context.just_pressed_keys.insert(keycode);
@@ -6,4 +6,9 @@ impl<S> Gamma<S> {
     pub fn delta_time(&mut self) -> Duration {
         self.delta
     }
+
+    /// Time since the first frame.
+    pub fn elapsed_time(&self) -> Duration {
+        self.elapsed
+    }
 }
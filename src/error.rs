@@ -0,0 +1,78 @@
+use std::{fmt, path::PathBuf};
+
+/// Everything that can go wrong while starting the engine or loading and saving assets.
+#[derive(Debug)]
+pub enum GammaError {
+    /// `run` was called without registering game state with `on_init`.
+    MissingInit,
+    /// The event loop or the window could not be created.
+    Window(String),
+    /// No graphics adapter matches the requested options.
+    Adapter(String),
+    /// The graphics device could not be created.
+    Device(String),
+    /// The window surface could not be created or read from.
+    Surface(String),
+    /// The audio output could not be opened.
+    Audio(String),
+    /// An image or sound could not be decoded.
+    Decode(String),
+    /// An image could not be encoded.
+    Encode(String),
+    /// A file could not be read or written.
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// A GPU operation failed, such as reading a frame back.
+    Render(String),
+    /// A shader could not be compiled.
+    Shader(String),
+    /// An engine function was called before rendering was set up.
+    NotInitialized(&'static str),
+    /// An argument was out of range, such as a region outside of a texture.
+    InvalidInput(String),
+}
+
+impl fmt::Display for GammaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GammaError::MissingInit => write!(
+                f,
+                "Cannot call draw or update without init, please register game state with the `on_init` builder method"
+            ),
+            GammaError::Window(message) => write!(f, "Window error: {}", message),
+            GammaError::Adapter(message) => write!(f, "Adapter error: {}", message),
+            GammaError::Device(message) => write!(f, "Device error: {}", message),
+            GammaError::Surface(message) => write!(f, "Surface error: {}", message),
+            GammaError::Audio(message) => write!(f, "Audio error: {}", message),
+            GammaError::Decode(message) => write!(f, "Decode error: {}", message),
+            GammaError::Encode(message) => write!(f, "Encode error: {}", message),
+            GammaError::Io { path, source } => {
+                write!(f, "IO error for '{}': {}", path.display(), source)
+            }
+            GammaError::Render(message) => write!(f, "Render error: {}", message),
+            GammaError::Shader(message) => write!(f, "Shader error: {}", message),
+            GammaError::NotInitialized(what) => write!(f, "{} not initialized", what),
+            GammaError::InvalidInput(message) => write!(f, "Invalid input: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for GammaError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            GammaError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl GammaError {
+    pub(crate) fn io(path: impl Into<PathBuf>, source: std::io::Error) -> Self {
+        GammaError::Io {
+            path: path.into(),
+            source,
+        }
+    }
+}
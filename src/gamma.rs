@@ -1,14 +1,39 @@
-use rodio::{OutputStream, OutputStreamBuilder};
+use rodio::OutputStream;
 
 use std::time::{Duration, Instant};
-use std::{collections::HashSet, sync::Arc};
-use wgpu::{Adapter, Device, Instance, Queue, Surface, SurfaceConfiguration};
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+    sync::Arc,
+};
+use wgpu::{Adapter, Device, Instance, Queue, Surface, SurfaceConfiguration, Texture};
 
-use winit::{dpi::LogicalSize, keyboard::KeyCode, window::Window};
+use winit::{
+    dpi::{LogicalPosition, LogicalSize},
+    keyboard::KeyCode,
+    window::Window,
+};
 
 use crate::{
     builder::InitFn,
-    rendering::{TexturePipeline, frame::Frame},
+    engine::Controller,
+    error::GammaError,
+    rendering::{
+        MaskMode, MipmapPipeline, PresentMode, RenderStats, TexturePipeline, Viewport,
+        adapter::AdapterOptions,
+        clip::ClipRect,
+        debug::DebugLayer,
+        frame::Frame,
+        instancing::InstancePipeline,
+        lighting::LightingState,
+        mesh::MeshPipeline,
+        recording::Recorder,
+        registry::TextureRegistry,
+        sdf_text::SdfTextPipeline,
+        sprite_effect::SpriteEffectPipeline,
+        text::TextRenderer,
+        windows::{ExtraWindow, WindowId, WindowRequest},
+    },
 };
 
 pub type UpdateFn<S> = fn(&mut Gamma<S>, &mut S);
@@ -18,7 +43,15 @@ pub struct Gamma<S> {
     // Internal
     pub(crate) last_frame_time: std::time::Instant,
     pub(crate) delta: Duration,
+    /// Time since the first frame, the sum of every frame's delta.
+    pub(crate) elapsed: Duration,
     pub(crate) current_frame: Option<Frame>,
+    pub(crate) clip_stack: Vec<ClipRect>,
+    pub(crate) viewport: Option<Viewport>,
+    pub(crate) writing_mask: bool,
+    pub(crate) mask_mode: MaskMode,
+    pub(crate) frame_stats: RenderStats,
+    pub(crate) render_stats: RenderStats,
 
     // User Provided
     pub(crate) draw_fn: DrawFn<S>,
@@ -30,8 +63,23 @@ pub struct Gamma<S> {
     pub(crate) logical_size: LogicalSize<f64>,
     pub(crate) resizable: bool,
     pub(crate) vsync: bool,
+    pub(crate) present_mode: Option<PresentMode>,
+    pub(crate) max_frame_latency: u32,
+    pub(crate) adapter_options: AdapterOptions,
     pub(crate) fullscreen: bool,
+    pub(crate) transparent: bool,
+    pub(crate) decorations: bool,
+    pub(crate) always_on_top: bool,
+    /// The PNG encoded window icon, decoded when the window is created.
+    pub(crate) icon: Option<Vec<u8>>,
+    pub(crate) position: Option<LogicalPosition<f64>>,
+    pub(crate) min_size: Option<LogicalSize<f64>>,
+    pub(crate) max_size: Option<LogicalSize<f64>>,
     pub(crate) close_on_escape: bool,
+    pub(crate) show_stats_overlay: bool,
+    pub(crate) debug_key: Option<KeyCode>,
+    /// The sample count asked for with `with_msaa`.
+    pub(crate) msaa: u32,
 
     // Rendering
     pub(crate) window: Option<Arc<Window>>,
@@ -42,24 +90,60 @@ pub struct Gamma<S> {
     pub(crate) queue: Option<Queue>,
     pub(crate) adapter: Option<Adapter>,
     pub(crate) texture_pipeline: Option<TexturePipeline>,
+    pub(crate) mipmap_pipeline: Option<MipmapPipeline>,
+    pub(crate) instance_pipeline: Option<InstancePipeline>,
+    pub(crate) mesh_pipeline: Option<MeshPipeline>,
+    pub(crate) headless_target: Option<Texture>,
+    pub(crate) stencil_texture: Option<Texture>,
+    /// The sample count in use, which may be lower than `msaa` when the adapter lacks support.
+    pub(crate) sample_count: u32,
+    pub(crate) msaa_texture: Option<Texture>,
+    pub(crate) text_renderer: Option<TextRenderer>,
+    pub(crate) sdf_text_pipeline: Option<SdfTextPipeline>,
+    pub(crate) sprite_effect_pipeline: Option<SpriteEffectPipeline>,
+    /// Errors of shaders that failed to reload, shown on screen at the end of the frame.
+    pub(crate) shader_errors: Vec<String>,
+    pub(crate) lighting: LightingState,
+    pub(crate) textures: TextureRegistry,
+    pub(crate) debug_layer: DebugLayer,
+    pub(crate) recorder: Option<Recorder>,
+    pub(crate) recording_result: Option<Result<PathBuf, GammaError>>,
+
+    // Extra Windows
+    pub(crate) extra_windows: Vec<ExtraWindow<S>>,
+    pub(crate) window_requests: Vec<WindowRequest<S>>,
+    pub(crate) windows_to_close: Vec<WindowId>,
+    /// Why windows requested with `open_window` could not be opened.
+    pub(crate) window_errors: HashMap<WindowId, GammaError>,
+    /// The index of the extra window being drawn, `None` while drawing the main window.
+    pub(crate) drawing_window: Option<usize>,
+    pub(crate) last_window_id: u32,
 
     // Audio
-    pub(crate) stream_handle: OutputStream,
+    pub(crate) stream_handle: Option<OutputStream>,
 
     // User Input
     pub(crate) pressed_keys: HashSet<KeyCode>,
     pub(crate) just_pressed_keys: HashSet<KeyCode>,
     pub(crate) just_released_keys: HashSet<KeyCode>,
+    /// The controller of every player, by player index.
+    pub(crate) controllers: Vec<Controller>,
 }
 
 impl<S> Default for Gamma<S> {
     fn default() -> Self {
-        let stream_handle = OutputStreamBuilder::open_default_stream().unwrap();
         Self {
             // Internal
             last_frame_time: Instant::now(),
             delta: Duration::ZERO,
+            elapsed: Duration::ZERO,
             current_frame: None,
+            clip_stack: Vec::new(),
+            viewport: None,
+            writing_mask: false,
+            mask_mode: MaskMode::Disabled,
+            frame_stats: RenderStats::default(),
+            render_stats: RenderStats::default(),
 
             // User Provided
             draw_fn: |_, _| {},
@@ -74,8 +158,21 @@ impl<S> Default for Gamma<S> {
             },
             resizable: true,
             vsync: true,
+            present_mode: None,
+            max_frame_latency: 2,
+            adapter_options: AdapterOptions::default(),
             fullscreen: false,
+            transparent: false,
+            decorations: true,
+            always_on_top: false,
+            icon: None,
+            position: None,
+            min_size: None,
+            max_size: None,
             close_on_escape: false,
+            show_stats_overlay: false,
+            debug_key: None,
+            msaa: 1,
 
             // Rendering
             window: Default::default(),
@@ -86,14 +183,39 @@ impl<S> Default for Gamma<S> {
             queue: None,
             adapter: None,
             texture_pipeline: None,
+            mipmap_pipeline: None,
+            instance_pipeline: None,
+            mesh_pipeline: None,
+            headless_target: None,
+            stencil_texture: None,
+            sample_count: 1,
+            msaa_texture: None,
+            text_renderer: None,
+            sdf_text_pipeline: None,
+            sprite_effect_pipeline: None,
+            shader_errors: Vec::new(),
+            lighting: LightingState::default(),
+            textures: TextureRegistry::default(),
+            debug_layer: DebugLayer::default(),
+            recorder: None,
+            recording_result: None,
+
+            // Extra Windows
+            extra_windows: Vec::new(),
+            window_requests: Vec::new(),
+            windows_to_close: Vec::new(),
+            window_errors: HashMap::new(),
+            drawing_window: None,
+            last_window_id: 0,
 
             // Audio
-            stream_handle,
+            stream_handle: None,
 
             // User Input
             pressed_keys: Default::default(),
             just_pressed_keys: Default::default(),
             just_released_keys: Default::default(),
+            controllers: Vec::new(),
         }
     }
 }
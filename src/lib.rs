@@ -1,13 +1,23 @@
 mod audio;
 mod builder;
 mod engine;
+mod error;
 pub mod gamma;
 mod rendering;
 
 pub mod prelude {
     pub use crate::audio::Sound;
     pub use crate::builder::GammaBuilder;
+    pub use crate::engine::Controller;
+    pub use crate::error::GammaError;
     pub use crate::gamma::Gamma;
-    pub use crate::rendering::{Flip, Texture};
+    pub use crate::rendering::{
+        Camera, Color, DebugDraw, Flip, Light, LightKind, LineCap, LineJoin, LineStyle, MaskMode,
+        Mesh, MeshUsage, MeshVertex, PixelBuffer, PolylinePoint, PresentMode, RecordingFormat,
+        RecordingOptions, RenderStats, SdfFont, Shader, SpriteEffect, SpriteInstance, TextStyle,
+        Texture, TextureId, Trail, Viewport, WindowId,
+    };
+    pub use image::RgbaImage;
+    pub use wgpu::{Backends, Features, Limits, PowerPreference};
     pub use winit::keyboard::KeyCode;
 }
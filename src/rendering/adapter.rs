@@ -0,0 +1,130 @@
+use wgpu::{
+    Adapter, Backends, Device, Features, Instance, Limits, PowerPreference, Queue, Surface,
+};
+
+use crate::error::GammaError;
+
+/// Options used to pick the graphics adapter and create the device.
+#[derive(Debug, Clone)]
+pub(crate) struct AdapterOptions {
+    pub power_preference: PowerPreference,
+    pub backends: Backends,
+    pub force_fallback_adapter: bool,
+    pub required_features: Features,
+    pub required_limits: Limits,
+}
+
+impl Default for AdapterOptions {
+    fn default() -> Self {
+        Self {
+            power_preference: PowerPreference::HighPerformance,
+            backends: Backends::all(),
+            force_fallback_adapter: false,
+            required_features: Features::empty(),
+            required_limits: Limits::default(),
+        }
+    }
+}
+
+impl AdapterOptions {
+    pub fn create_instance(&self) -> Instance {
+        Instance::new(&wgpu::InstanceDescriptor {
+            backends: self.backends,
+            ..Default::default()
+        })
+    }
+
+    /// Find an adapter that meets every option, trying wgpu's own choice first and then
+    /// every other adapter it knows about.
+    pub fn request_adapter(
+        &self,
+        instance: &Instance,
+        surface: Option<&Surface<'static>>,
+    ) -> Result<Adapter, GammaError> {
+        let requested =
+            pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: self.power_preference,
+                compatible_surface: surface,
+                force_fallback_adapter: self.force_fallback_adapter,
+            }));
+
+        if let Ok(adapter) = requested
+            && self.problems(&adapter, surface).is_empty()
+        {
+            return Ok(adapter);
+        }
+
+        let adapters = pollster::block_on(instance.enumerate_adapters(self.backends));
+        let mut report = String::new();
+
+        for adapter in adapters {
+            let problems = self.problems(&adapter, surface);
+            if problems.is_empty() {
+                return Ok(adapter);
+            }
+
+            let info = adapter.get_info();
+            report.push_str(&format!(
+                "\n  - {} ({:?}, {:?}): {}",
+                info.name,
+                info.backend,
+                info.device_type,
+                problems.join(", ")
+            ));
+        }
+
+        if report.is_empty() {
+            report.push_str("\n  (none)");
+        }
+
+        let backends: Vec<_> = self.backends.iter_names().map(|(name, _)| name).collect();
+        Err(GammaError::Adapter(format!(
+            "No graphics adapter matches the requested options on backends {}. Available adapters:{}",
+            backends.join(" | "),
+            report
+        )))
+    }
+
+    pub fn request_device(&self, adapter: &Adapter) -> Result<(Device, Queue), GammaError> {
+        pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor {
+            required_features: self.required_features,
+            required_limits: self.required_limits.clone(),
+            ..Default::default()
+        }))
+        .map_err(|e| GammaError::Device(format!("Failed to create a device: {}", e)))
+    }
+
+    /// Every reason the adapter cannot be used, or nothing when it fits.
+    fn problems(&self, adapter: &Adapter, surface: Option<&Surface<'static>>) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        if let Some(surface) = surface
+            && !adapter.is_surface_supported(surface)
+        {
+            problems.push("cannot present to the window".to_string());
+        }
+
+        if self.force_fallback_adapter && adapter.get_info().device_type != wgpu::DeviceType::Cpu {
+            problems.push("not a software fallback adapter".to_string());
+        }
+
+        let missing = self.required_features - adapter.features();
+        if !missing.is_empty() {
+            let names: Vec<_> = missing.iter_names().map(|(name, _)| name).collect();
+            problems.push(format!("missing features {}", names.join(" | ")));
+        }
+
+        self.required_limits.check_limits_with_fail_fn(
+            &adapter.limits(),
+            false,
+            |name, wanted, allowed| {
+                problems.push(format!(
+                    "{} is {} but {} is required",
+                    name, allowed, wanted
+                ));
+            },
+        );
+
+        problems
+    }
+}
@@ -1,31 +1,30 @@
-use crate::{gamma::Gamma, rendering::Frame};
+use crate::{gamma::Gamma, rendering::Color};
 
 impl<S> Gamma<S> {
-    pub fn clear_screen(&mut self, r: u8, g: u8, b: u8) {
-        let (surface, device, queue) = match (
-            self.surface.as_ref(),
-            self.device.as_ref(),
-            self.queue.as_ref(),
-        ) {
-            (Some(s), Some(d), Some(q)) => (s, d, q),
-            _ => return,
-        };
+    /// Fill the screen with a color. Only the first clear of a frame has an effect.
+    ///
+    /// A translucent color shows the desktop through a window made with `with_transparent`,
+    /// and is drawn over black otherwise.
+    pub fn clear_screen(&mut self, color: impl Into<Color>) {
+        // The frame holds premultiplied colors, as the compositor of a transparent window expects.
+        let color = color.into().to_linear();
+        let alpha = color.a as f64;
 
-        if self.current_frame.is_none() {
-            let texture = match surface.get_current_texture() {
-                Ok(t) => t,
-                Err(e) => {
-                    eprintln!("Failed to get surface texture: {:?}", e);
-                    return;
-                }
-            };
-
-            self.current_frame = Some(Frame::new(texture));
+        if !self.acquire_frame() {
+            return;
         }
 
+        let (device, queue) = match (self.device.as_ref(), self.queue.as_ref()) {
+            (Some(d), Some(q)) => (d, q),
+            _ => return,
+        };
+
         let frame = self.current_frame.as_mut().unwrap();
 
         if !frame.cleared {
+            // Clearing the screen also clears the mask.
+            frame.stencil.cleared.set(false);
+
             let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
                 label: Some("Clear Encoder"),
             });
@@ -33,21 +32,15 @@ impl<S> Gamma<S> {
             {
                 let _render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                     label: Some("Clear Pass"),
-                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                        view: &frame.view,
-                        resolve_target: None,
-                        ops: wgpu::Operations {
-                            load: wgpu::LoadOp::Clear(wgpu::Color {
-                                r: r as f64 / 255.0,
-                                g: g as f64 / 255.0,
-                                b: b as f64 / 255.0,
-                                a: 1.0,
-                            }),
-                            store: wgpu::StoreOp::Store,
+                    color_attachments: &[Some(frame.color_attachment(wgpu::LoadOp::Clear(
+                        wgpu::Color {
+                            r: color.r as f64 * alpha,
+                            g: color.g as f64 * alpha,
+                            b: color.b as f64 * alpha,
+                            a: alpha,
                         },
-                        depth_slice: None,
-                    })],
-                    depth_stencil_attachment: None,
+                    )))],
+                    depth_stencil_attachment: frame.depth_stencil_attachment(),
                     timestamp_writes: None,
                     occlusion_query_set: None,
                     multiview_mask: None,
@@ -56,6 +49,7 @@ impl<S> Gamma<S> {
 
             queue.submit(std::iter::once(encoder.finish()));
             frame.cleared = true;
+            self.frame_stats.batches += 1;
         }
     }
 }
@@ -0,0 +1,107 @@
+use crate::{gamma::Gamma, rendering::frame::Frame};
+
+/// A rectangle in pixels that drawing is limited to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct ClipRect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl ClipRect {
+    fn intersect(&self, other: &ClipRect) -> ClipRect {
+        let left = self.x.max(other.x);
+        let top = self.y.max(other.y);
+        let right = (self.x + self.width).min(other.x + other.width);
+        let bottom = (self.y + self.height).min(other.y + other.height);
+
+        ClipRect {
+            x: left,
+            y: top,
+            width: (right - left).max(0.0),
+            height: (bottom - top).max(0.0),
+        }
+    }
+}
+
+/// A scissor rectangle in whole pixels that fits inside the frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Scissor {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Scissor {
+    pub fn apply(&self, render_pass: &mut wgpu::RenderPass) {
+        render_pass.set_scissor_rect(self.x, self.y, self.width, self.height);
+    }
+}
+
+impl<S> Gamma<S> {
+    /// Limit everything drawn after this call to a rectangle, until the matching `pop_clip_rect`.
+    ///
+    /// The rectangle is in the same pixel coordinates as `draw_texture`, including any viewport
+    /// camera. When clip rects are nested, drawing is limited to the area inside all of them.
+    /// `clear_screen` always clears the whole frame.
+    pub fn push_clip_rect(&mut self, x: f32, y: f32, width: f32, height: f32) {
+        let view = self.view();
+        let (x, y) = view.point(x, y);
+        let rect = ClipRect {
+            x,
+            y,
+            width: view.length(width).max(0.0),
+            height: view.length(height).max(0.0),
+        };
+
+        let rect = match self.clip_stack.last() {
+            Some(parent) => parent.intersect(&rect),
+            None => rect,
+        };
+
+        self.clip_stack.push(rect);
+    }
+
+    /// Remove the clip rect added by the last call to `push_clip_rect`.
+    pub fn pop_clip_rect(&mut self) {
+        self.clip_stack.pop();
+    }
+
+    /// The scissor rect for the current clip rect and viewport, or `None` when nothing drawn
+    /// would be visible.
+    pub(crate) fn current_scissor(&self, frame: &Frame) -> Option<Scissor> {
+        let (frame_width, frame_height) = (frame.texture.width(), frame.texture.height());
+
+        let rect = match (self.clip_stack.last(), &self.viewport) {
+            (Some(clip), Some(viewport)) => clip.intersect(&viewport.rect()),
+            (Some(clip), None) => *clip,
+            (None, Some(viewport)) => viewport.rect(),
+            (None, None) => {
+                return Some(Scissor {
+                    x: 0,
+                    y: 0,
+                    width: frame_width,
+                    height: frame_height,
+                });
+            }
+        };
+
+        let left = (rect.x.floor().max(0.0) as u32).min(frame_width);
+        let top = (rect.y.floor().max(0.0) as u32).min(frame_height);
+        let right = ((rect.x + rect.width).ceil().max(0.0) as u32).min(frame_width);
+        let bottom = ((rect.y + rect.height).ceil().max(0.0) as u32).min(frame_height);
+
+        if right <= left || bottom <= top {
+            return None;
+        }
+
+        Some(Scissor {
+            x: left,
+            y: top,
+            width: right - left,
+            height: bottom - top,
+        })
+    }
+}
@@ -0,0 +1,271 @@
+use crate::error::GammaError;
+
+/// A color with red, green, blue and alpha from 0.0 to 1.0.
+///
+/// The color channels are in sRGB, the space used by image editors and hex codes, so a color
+/// picked in one shows up the same on screen. They are converted to linear light when drawn.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Color {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+
+impl Color {
+    pub const TRANSPARENT: Color = Color::new(0.0, 0.0, 0.0, 0.0);
+    pub const BLACK: Color = Color::rgb(0.0, 0.0, 0.0);
+    pub const WHITE: Color = Color::rgb(1.0, 1.0, 1.0);
+    pub const GRAY: Color = Color::rgb(0.5, 0.5, 0.5);
+    pub const RED: Color = Color::rgb(1.0, 0.0, 0.0);
+    pub const GREEN: Color = Color::rgb(0.0, 1.0, 0.0);
+    pub const BLUE: Color = Color::rgb(0.0, 0.0, 1.0);
+    pub const YELLOW: Color = Color::rgb(1.0, 1.0, 0.0);
+    pub const CYAN: Color = Color::rgb(0.0, 1.0, 1.0);
+    pub const MAGENTA: Color = Color::rgb(1.0, 0.0, 1.0);
+    pub const ORANGE: Color = Color::rgb(1.0, 0.5, 0.0);
+    pub const PURPLE: Color = Color::rgb(0.5, 0.0, 1.0);
+
+    pub const fn new(r: f32, g: f32, b: f32, a: f32) -> Self {
+        Self { r, g, b, a }
+    }
+
+    /// An opaque color.
+    pub const fn rgb(r: f32, g: f32, b: f32) -> Self {
+        Self::new(r, g, b, 1.0)
+    }
+
+    /// An opaque color from channels between 0 and 255.
+    pub const fn rgb8(r: u8, g: u8, b: u8) -> Self {
+        Self::rgba8(r, g, b, 255)
+    }
+
+    /// A color from channels between 0 and 255.
+    pub const fn rgba8(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Self::new(
+            r as f32 / 255.0,
+            g as f32 / 255.0,
+            b as f32 / 255.0,
+            a as f32 / 255.0,
+        )
+    }
+
+    /// Parse a hex color such as `#ff8800`, in the form `RGB`, `RGBA`, `RRGGBB` or `RRGGBBAA`
+    /// with an optional leading `#`.
+    pub fn from_hex(hex: &str) -> Result<Self, GammaError> {
+        let invalid = || GammaError::InvalidInput(format!("'{}' is not a hex color", hex));
+
+        let digits = hex.strip_prefix('#').unwrap_or(hex);
+        if !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(invalid());
+        }
+
+        let channel = |i: usize, width: usize| {
+            let value = u8::from_str_radix(&digits[i * width..(i + 1) * width], 16)
+                .map_err(|_| invalid())?;
+            // A single digit stands for itself repeated, so `f` is `ff`.
+            Ok(if width == 1 { value * 17 } else { value })
+        };
+
+        match digits.len() {
+            3 => Ok(Self::rgb8(channel(0, 1)?, channel(1, 1)?, channel(2, 1)?)),
+            4 => Ok(Self::rgba8(
+                channel(0, 1)?,
+                channel(1, 1)?,
+                channel(2, 1)?,
+                channel(3, 1)?,
+            )),
+            6 => Ok(Self::rgb8(channel(0, 2)?, channel(1, 2)?, channel(2, 2)?)),
+            8 => Ok(Self::rgba8(
+                channel(0, 2)?,
+                channel(1, 2)?,
+                channel(2, 2)?,
+                channel(3, 2)?,
+            )),
+            _ => Err(invalid()),
+        }
+    }
+
+    /// The color as `#rrggbbaa`.
+    pub fn to_hex(self) -> String {
+        let [r, g, b, a] = self.to_rgba8();
+        format!("#{:02x}{:02x}{:02x}{:02x}", r, g, b, a)
+    }
+
+    /// An opaque color from a hue in degrees, and saturation and value from 0.0 to 1.0.
+    pub fn from_hsv(hue: f32, saturation: f32, value: f32) -> Self {
+        let chroma = value * saturation;
+        let (r, g, b) = hue_to_rgb(hue, chroma);
+        let offset = value - chroma;
+        Self::rgb(r + offset, g + offset, b + offset)
+    }
+
+    /// The hue in degrees, and saturation and value from 0.0 to 1.0.
+    pub fn to_hsv(self) -> (f32, f32, f32) {
+        let (hue, max, min) = self.hue_max_min();
+        let saturation = if max == 0.0 { 0.0 } else { (max - min) / max };
+        (hue, saturation, max)
+    }
+
+    /// An opaque color from a hue in degrees, and saturation and lightness from 0.0 to 1.0.
+    pub fn from_hsl(hue: f32, saturation: f32, lightness: f32) -> Self {
+        let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+        let (r, g, b) = hue_to_rgb(hue, chroma);
+        let offset = lightness - chroma / 2.0;
+        Self::rgb(r + offset, g + offset, b + offset)
+    }
+
+    /// The hue in degrees, and saturation and lightness from 0.0 to 1.0.
+    pub fn to_hsl(self) -> (f32, f32, f32) {
+        let (hue, max, min) = self.hue_max_min();
+        let lightness = (max + min) / 2.0;
+        let saturation = if max == min {
+            0.0
+        } else {
+            (max - min) / (1.0 - (2.0 * lightness - 1.0).abs())
+        };
+        (hue, saturation, lightness)
+    }
+
+    pub fn with_alpha(mut self, a: f32) -> Self {
+        self.a = a;
+        self
+    }
+
+    /// Blend towards another color, with `t` from 0.0 for this color to 1.0 for the other.
+    pub fn lerp(self, other: Color, t: f32) -> Self {
+        Self::new(
+            self.r + (other.r - self.r) * t,
+            self.g + (other.g - self.g) * t,
+            self.b + (other.b - self.b) * t,
+            self.a + (other.a - self.a) * t,
+        )
+    }
+
+    /// Convert the sRGB color channels to linear light, leaving alpha as it is.
+    pub fn to_linear(self) -> Self {
+        Self::new(
+            srgb_to_linear(self.r),
+            srgb_to_linear(self.g),
+            srgb_to_linear(self.b),
+            self.a,
+        )
+    }
+
+    /// Convert color channels in linear light back to sRGB, leaving alpha as it is.
+    pub fn from_linear(linear: Color) -> Self {
+        Self::new(
+            linear_to_srgb(linear.r),
+            linear_to_srgb(linear.g),
+            linear_to_srgb(linear.b),
+            linear.a,
+        )
+    }
+
+    /// The channels between 0 and 255.
+    pub fn to_rgba8(self) -> [u8; 4] {
+        let to_u8 = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+        [to_u8(self.r), to_u8(self.g), to_u8(self.b), to_u8(self.a)]
+    }
+
+    pub fn to_array(self) -> [f32; 4] {
+        [self.r, self.g, self.b, self.a]
+    }
+
+    /// The color as drawn on the GPU, in linear light.
+    pub(crate) fn to_linear_array(self) -> [f32; 4] {
+        self.to_linear().to_array()
+    }
+
+    fn hue_max_min(self) -> (f32, f32, f32) {
+        let max = self.r.max(self.g).max(self.b);
+        let min = self.r.min(self.g).min(self.b);
+        let delta = max - min;
+
+        let hue = if delta == 0.0 {
+            0.0
+        } else if max == self.r {
+            60.0 * ((self.g - self.b) / delta).rem_euclid(6.0)
+        } else if max == self.g {
+            60.0 * ((self.b - self.r) / delta + 2.0)
+        } else {
+            60.0 * ((self.r - self.g) / delta + 4.0)
+        };
+
+        (hue, max, min)
+    }
+}
+
+/// The red, green and blue of a fully saturated hue scaled to the chroma, before lightening.
+fn hue_to_rgb(hue: f32, chroma: f32) -> (f32, f32, f32) {
+    let sector = hue.rem_euclid(360.0) / 60.0;
+    let x = chroma * (1.0 - (sector % 2.0 - 1.0).abs());
+
+    match sector as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    }
+}
+
+fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+impl From<[f32; 4]> for Color {
+    fn from([r, g, b, a]: [f32; 4]) -> Self {
+        Self::new(r, g, b, a)
+    }
+}
+
+impl From<[f32; 3]> for Color {
+    fn from([r, g, b]: [f32; 3]) -> Self {
+        Self::rgb(r, g, b)
+    }
+}
+
+impl From<[u8; 4]> for Color {
+    fn from([r, g, b, a]: [u8; 4]) -> Self {
+        Self::rgba8(r, g, b, a)
+    }
+}
+
+impl From<[u8; 3]> for Color {
+    fn from([r, g, b]: [u8; 3]) -> Self {
+        Self::rgb8(r, g, b)
+    }
+}
+
+impl From<(u8, u8, u8)> for Color {
+    fn from((r, g, b): (u8, u8, u8)) -> Self {
+        Self::rgb8(r, g, b)
+    }
+}
+
+impl From<(u8, u8, u8, u8)> for Color {
+    fn from((r, g, b, a): (u8, u8, u8, u8)) -> Self {
+        Self::rgba8(r, g, b, a)
+    }
+}
+
+impl From<Color> for [f32; 4] {
+    fn from(color: Color) -> Self {
+        color.to_array()
+    }
+}
@@ -1,59 +1,125 @@
+use crate::error::GammaError;
 use crate::gamma::Gamma;
-use crate::rendering::TexturePipeline;
+use crate::rendering::{MipmapPipeline, TexturePipeline, msaa::supported_sample_count};
 use std::sync::Arc;
-use wgpu::{Instance, PresentMode, SurfaceConfiguration, TextureFormat, TextureUsages};
+use wgpu::{SurfaceCapabilities, SurfaceConfiguration, TextureFormat, TextureUsages};
 use winit::event_loop::ActiveEventLoop;
-use winit::window::{Fullscreen, Window};
+use winit::window::{Fullscreen, Icon, Window, WindowLevel};
 
-pub(crate) fn initialize_rendering<S>(gamma: &mut Gamma<S>, event_loop: &ActiveEventLoop) {
+/// How finished frames are handed to the display.
+///
+/// Modes the adapter does not support fall back to `Fifo`, which is available everywhere.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum PresentMode {
+    /// Wait for the display's vertical blank. No tearing, frames are limited to the refresh rate.
+    Fifo,
+    /// Replace the waiting frame with the newest one. No tearing and low latency, but frames may be dropped.
+    Mailbox,
+    /// Present as soon as a frame is ready. Lowest latency, but may tear.
+    Immediate,
+}
+
+impl PresentMode {
+    fn to_wgpu(self) -> wgpu::PresentMode {
+        match self {
+            PresentMode::Fifo => wgpu::PresentMode::Fifo,
+            PresentMode::Mailbox => wgpu::PresentMode::Mailbox,
+            PresentMode::Immediate => wgpu::PresentMode::Immediate,
+        }
+    }
+}
+
+pub(crate) fn initialize_rendering<S>(
+    gamma: &mut Gamma<S>,
+    event_loop: &ActiveEventLoop,
+) -> Result<(), GammaError> {
     let title = gamma.title.clone();
     let window_size = gamma.logical_size;
 
     let mut window_attributes = Window::default_attributes()
         .with_title(title)
         .with_inner_size(window_size)
-        .with_resizable(gamma.resizable);
+        .with_resizable(gamma.resizable)
+        .with_transparent(gamma.transparent)
+        .with_decorations(gamma.decorations);
 
     if gamma.fullscreen {
         window_attributes = window_attributes.with_fullscreen(Some(Fullscreen::Borderless(None)));
     }
+    if gamma.always_on_top {
+        window_attributes = window_attributes.with_window_level(WindowLevel::AlwaysOnTop);
+    }
+    if let Some(png) = &gamma.icon {
+        window_attributes = window_attributes.with_window_icon(Some(decode_icon(png)?));
+    }
+    if let Some(position) = gamma.position {
+        window_attributes = window_attributes.with_position(position);
+    }
+    if let Some(min_size) = gamma.min_size {
+        window_attributes = window_attributes.with_min_inner_size(min_size);
+    }
+    if let Some(max_size) = gamma.max_size {
+        window_attributes = window_attributes.with_max_inner_size(max_size);
+    }
 
-    let window = Arc::new(event_loop.create_window(window_attributes).unwrap());
+    let window = Arc::new(
+        event_loop
+            .create_window(window_attributes)
+            .map_err(|e| GammaError::Window(format!("Failed to create the window: {}", e)))?,
+    );
     let size = window.inner_size();
 
     // Initialize wgpu
-    let instance = Instance::default();
-    let surface = instance.create_surface(window.clone()).unwrap();
+    let instance = gamma.adapter_options.create_instance();
+    let surface = instance
+        .create_surface(window.clone())
+        .map_err(|e| GammaError::Surface(format!("Failed to create the surface: {}", e)))?;
 
-    let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
-        power_preference: wgpu::PowerPreference::HighPerformance,
-        compatible_surface: Some(&surface),
-        force_fallback_adapter: false,
-    }))
-    .unwrap();
+    let adapter = gamma
+        .adapter_options
+        .request_adapter(&instance, Some(&surface))?;
+    let (device, queue) = gamma.adapter_options.request_device(&adapter)?;
 
-    let (device, queue) =
-        pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor::default())).unwrap();
+    let capabilities = surface.get_capabilities(&adapter);
+    let format = choose_surface_format(&capabilities);
 
-    let present_mode = if gamma.vsync {
-        PresentMode::AutoVsync
+    let present_mode = match gamma.present_mode {
+        Some(mode) if capabilities.present_modes.contains(&mode.to_wgpu()) => mode.to_wgpu(),
+        Some(_) => wgpu::PresentMode::Fifo,
+        None if gamma.vsync => wgpu::PresentMode::AutoVsync,
+        None => wgpu::PresentMode::AutoNoVsync,
+    };
+
+    // Frames hold premultiplied colors, so transparent windows blend with the desktop correctly.
+    let preferred_alpha_mode = if gamma.transparent {
+        wgpu::CompositeAlphaMode::PreMultiplied
+    } else {
+        wgpu::CompositeAlphaMode::Opaque
+    };
+    let alpha_mode = if capabilities.alpha_modes.contains(&preferred_alpha_mode) {
+        preferred_alpha_mode
     } else {
-        PresentMode::AutoNoVsync
+        capabilities.alpha_modes[0]
     };
 
+    // COPY_SRC lets screenshots read the presented frame back, when the surface allows it.
+    let usage = TextureUsages::RENDER_ATTACHMENT | (capabilities.usages & TextureUsages::COPY_SRC);
+
     let config = SurfaceConfiguration {
-        usage: TextureUsages::RENDER_ATTACHMENT,
-        format: TextureFormat::Bgra8UnormSrgb,
-        view_formats: vec![TextureFormat::Bgra8UnormSrgb],
-        desired_maximum_frame_latency: 2,
+        usage,
+        format,
+        view_formats: vec![format],
+        desired_maximum_frame_latency: gamma.max_frame_latency,
         width: size.width,
         height: size.height,
         present_mode,
-        alpha_mode: wgpu::CompositeAlphaMode::Auto,
+        alpha_mode,
     };
 
     surface.configure(&device, &config);
-    let texture_pipeline = TexturePipeline::new(&device, config.format);
+    let sample_count = supported_sample_count(&adapter, config.format, gamma.msaa);
+    let texture_pipeline = TexturePipeline::new(&device, config.format, sample_count);
+    let mipmap_pipeline = MipmapPipeline::new(&device);
 
     gamma.window = Some(window);
     gamma.instance = Some(instance);
@@ -62,5 +128,30 @@ pub(crate) fn initialize_rendering<S>(gamma: &mut Gamma<S>, event_loop: &ActiveE
     gamma.queue = Some(queue);
     gamma.adapter = Some(adapter);
     gamma.surface_config = Some(config);
+    gamma.sample_count = sample_count;
     gamma.texture_pipeline = Some(texture_pipeline);
+    gamma.mipmap_pipeline = Some(mipmap_pipeline);
+
+    Ok(())
+}
+
+/// Pick the first sRGB format the surface offers so colors are blended and displayed correctly,
+/// otherwise the surface's preferred format.
+fn choose_surface_format(capabilities: &SurfaceCapabilities) -> TextureFormat {
+    capabilities
+        .formats
+        .iter()
+        .copied()
+        .find(|format| format.is_srgb())
+        .unwrap_or(capabilities.formats[0])
+}
+
+fn decode_icon(png: &[u8]) -> Result<Icon, GammaError> {
+    let image = image::load_from_memory_with_format(png, image::ImageFormat::Png)
+        .map_err(|e| GammaError::Decode(format!("Failed to decode the window icon: {}", e)))?
+        .into_rgba8();
+    let (width, height) = image.dimensions();
+
+    Icon::from_rgba(image.into_raw(), width, height)
+        .map_err(|e| GammaError::Window(format!("Failed to set the window icon: {}", e)))
 }
@@ -0,0 +1,336 @@
+use std::time::Duration;
+
+use crate::{
+    gamma::Gamma,
+    rendering::{
+        Color, LineCap, LineStyle, Mesh, MeshUsage, PolylinePoint, polyline::tessellate_polyline,
+        text::TextRenderer, viewport::View,
+    },
+};
+
+const DEBUG_LINE_WIDTH: f32 = 2.0;
+const DEBUG_TEXT_SIZE: f32 = 16.0;
+const DEBUG_CIRCLE_SEGMENTS: usize = 32;
+
+/// Shapes queued with `debug`, drawn on top of the frame when debug drawing is enabled.
+#[derive(Default)]
+pub(crate) struct DebugLayer {
+    pub enabled: bool,
+    items: Vec<DebugItem>,
+}
+
+struct DebugItem {
+    shape: DebugShape,
+    /// How much longer the shape stays on screen after the current frame.
+    remaining: Duration,
+}
+
+enum DebugShape {
+    Rect {
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        color: Color,
+    },
+    Circle {
+        x: f32,
+        y: f32,
+        radius: f32,
+        color: Color,
+    },
+    Line {
+        start: [f32; 2],
+        end: [f32; 2],
+        color: Color,
+    },
+    Text {
+        text: String,
+        x: f32,
+        y: f32,
+        color: Color,
+    },
+}
+
+impl DebugShape {
+    /// The shape in frame pixels, from world pixels seen through `view`.
+    fn in_frame(self, view: &View) -> Self {
+        match self {
+            DebugShape::Rect {
+                x,
+                y,
+                width,
+                height,
+                color,
+            } => {
+                let (x, y) = view.point(x, y);
+                DebugShape::Rect {
+                    x,
+                    y,
+                    width: view.length(width),
+                    height: view.length(height),
+                    color,
+                }
+            }
+            DebugShape::Circle {
+                x,
+                y,
+                radius,
+                color,
+            } => {
+                let (x, y) = view.point(x, y);
+                DebugShape::Circle {
+                    x,
+                    y,
+                    radius: view.length(radius),
+                    color,
+                }
+            }
+            DebugShape::Line { start, end, color } => {
+                let (start, end) = (view.point(start[0], start[1]), view.point(end[0], end[1]));
+                DebugShape::Line {
+                    start: [start.0, start.1],
+                    end: [end.0, end.1],
+                    color,
+                }
+            }
+            DebugShape::Text { text, x, y, color } => {
+                let (x, y) = view.point(x, y);
+                DebugShape::Text { text, x, y, color }
+            }
+        }
+    }
+}
+
+/// Queues debug shapes, returned by `Gamma::debug`. Every call does nothing while debug drawing is off.
+pub struct DebugDraw<'a> {
+    layer: &'a mut DebugLayer,
+    duration: Duration,
+    /// Shapes are drawn after every viewport is done, so they are kept in frame pixels.
+    view: View,
+}
+
+impl DebugDraw<'_> {
+    /// Keep the following shapes on screen for a while instead of a single frame.
+    pub fn persist(mut self, duration: Duration) -> Self {
+        self.duration = duration;
+        self
+    }
+
+    /// The outline of a rectangle, such as a hitbox.
+    pub fn rect(
+        &mut self,
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        color: impl Into<Color>,
+    ) -> &mut Self {
+        self.push(DebugShape::Rect {
+            x,
+            y,
+            width,
+            height,
+            color: color.into(),
+        })
+    }
+
+    /// The outline of a circle around a center point.
+    pub fn circle(&mut self, x: f32, y: f32, radius: f32, color: impl Into<Color>) -> &mut Self {
+        self.push(DebugShape::Circle {
+            x,
+            y,
+            radius,
+            color: color.into(),
+        })
+    }
+
+    pub fn line(
+        &mut self,
+        x1: f32,
+        y1: f32,
+        x2: f32,
+        y2: f32,
+        color: impl Into<Color>,
+    ) -> &mut Self {
+        self.push(DebugShape::Line {
+            start: [x1, y1],
+            end: [x2, y2],
+            color: color.into(),
+        })
+    }
+
+    /// Text with its top left corner at a position.
+    pub fn text(
+        &mut self,
+        text: impl Into<String>,
+        x: f32,
+        y: f32,
+        color: impl Into<Color>,
+    ) -> &mut Self {
+        // Checked early too, so no string is allocated while debug drawing is off.
+        if !self.layer.enabled {
+            return self;
+        }
+
+        self.push(DebugShape::Text {
+            text: text.into(),
+            x,
+            y,
+            color: color.into(),
+        })
+    }
+
+    fn push(&mut self, shape: DebugShape) -> &mut Self {
+        if self.layer.enabled {
+            self.layer.items.push(DebugItem {
+                shape: shape.in_frame(&self.view),
+                remaining: self.duration,
+            });
+        }
+        self
+    }
+}
+
+impl<S> Gamma<S> {
+    /// Draw shapes for debugging, such as hitboxes and AI state, on top of everything else.
+    ///
+    /// Nothing is drawn unless debug drawing is on, which is toggled with the key given to
+    /// `with_debug_key` (no key by default) or with `set_debug_draw`. Shapes from every window
+    /// are drawn on the main window.
+    pub fn debug(&mut self) -> DebugDraw<'_> {
+        DebugDraw {
+            view: self.view(),
+            layer: &mut self.debug_layer,
+            duration: Duration::ZERO,
+        }
+    }
+
+    /// Turn debug drawing on or off. Turning it off removes any persisting shapes.
+    pub fn set_debug_draw(&mut self, enabled: bool) {
+        self.debug_layer.enabled = enabled;
+        if !enabled {
+            self.debug_layer.items.clear();
+        }
+    }
+
+    pub fn is_debug_draw_enabled(&self) -> bool {
+        self.debug_layer.enabled
+    }
+
+    /// Draw the queued debug shapes and drop the ones whose time is up.
+    pub(crate) fn draw_debug_layer(&mut self) {
+        if self.debug_layer.items.is_empty() {
+            return;
+        }
+
+        let items = std::mem::take(&mut self.debug_layer.items);
+        let style = LineStyle::default().with_cap(LineCap::Square);
+        let mut mesh = Mesh::new(Vec::new(), Vec::new(), MeshUsage::Dynamic);
+
+        for item in &items {
+            match item.shape {
+                DebugShape::Rect {
+                    x,
+                    y,
+                    width,
+                    height,
+                    color,
+                } => {
+                    let corners = [
+                        [x, y],
+                        [x + width, y],
+                        [x + width, y + height],
+                        [x, y + height],
+                        [x, y],
+                    ];
+                    tessellate_polyline(&mut mesh, &debug_points(&corners, color), style);
+                }
+                DebugShape::Circle {
+                    x,
+                    y,
+                    radius,
+                    color,
+                } => {
+                    let points: Vec<[f32; 2]> = (0..=DEBUG_CIRCLE_SEGMENTS)
+                        .map(|i| {
+                            let angle =
+                                i as f32 / DEBUG_CIRCLE_SEGMENTS as f32 * std::f32::consts::TAU;
+                            [x + angle.cos() * radius, y + angle.sin() * radius]
+                        })
+                        .collect();
+                    tessellate_polyline(&mut mesh, &debug_points(&points, color), style);
+                }
+                DebugShape::Line { start, end, color } => {
+                    tessellate_polyline(&mut mesh, &debug_points(&[start, end], color), style);
+                }
+                DebugShape::Text { .. } => {}
+            }
+        }
+
+        self.draw_mesh(&mut mesh, None);
+        self.draw_debug_text(&items);
+
+        let delta = self.delta;
+        self.debug_layer.items = items
+            .into_iter()
+            .filter(|item| item.remaining > delta)
+            .map(|item| DebugItem {
+                remaining: item.remaining - delta,
+                ..item
+            })
+            .collect();
+    }
+
+    fn draw_debug_text(&mut self, items: &[DebugItem]) {
+        if !items
+            .iter()
+            .any(|item| matches!(item.shape, DebugShape::Text { .. }))
+            || !self.acquire_frame()
+        {
+            return;
+        }
+
+        let sample_count = self.sample_count;
+
+        let (device, queue, frame) = match (
+            self.device.as_ref(),
+            self.queue.as_ref(),
+            self.current_frame.as_ref(),
+        ) {
+            (Some(d), Some(q), Some(f)) => (d, q, f),
+            _ => return,
+        };
+
+        let text_renderer = self
+            .text_renderer
+            .get_or_insert_with(|| TextRenderer::new(device, frame.texture.format(), sample_count));
+
+        for item in items {
+            if let DebugShape::Text {
+                ref text,
+                x,
+                y,
+                color,
+            } = item.shape
+            {
+                text_renderer.queue_with_shadow(
+                    text,
+                    x,
+                    y,
+                    DEBUG_TEXT_SIZE,
+                    color.to_linear_array(),
+                );
+            }
+        }
+
+        text_renderer.draw(device, queue, frame, &mut self.frame_stats);
+    }
+}
+
+fn debug_points(points: &[[f32; 2]], color: Color) -> Vec<PolylinePoint> {
+    points
+        .iter()
+        .map(|&[x, y]| PolylinePoint::new(x, y, DEBUG_LINE_WIDTH, color))
+        .collect()
+}
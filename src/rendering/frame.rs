@@ -1,21 +1,146 @@
-use wgpu::{SurfaceTexture, TextureView};
+use wgpu::{SurfaceTexture, Texture, TextureView};
+
+use crate::{
+    gamma::Gamma,
+    rendering::{MaskMode, stencil::FrameStencil},
+};
 
 pub struct Frame {
-    pub(crate) texture: SurfaceTexture,
+    /// Only set when drawing to a window, headless frames have nothing to present.
+    pub(crate) surface_texture: Option<SurfaceTexture>,
+    pub(crate) texture: Texture,
     pub(crate) view: TextureView,
+    pub(crate) stencil: FrameStencil,
+    /// Only set with MSAA on. Drawing goes here and is resolved into `view` after every pass.
+    pub(crate) msaa_view: Option<TextureView>,
     pub(crate) cleared: bool,
 }
 
 impl Frame {
-    pub fn new(texture: SurfaceTexture) -> Self {
-        let view = texture
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
+    pub fn new(
+        surface_texture: Option<SurfaceTexture>,
+        texture: Texture,
+        stencil: FrameStencil,
+        msaa_view: Option<TextureView>,
+    ) -> Self {
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
 
         Self {
+            surface_texture,
             texture,
             view,
+            stencil,
+            msaa_view,
             cleared: false,
         }
     }
+
+    pub fn present(self) {
+        if let Some(surface_texture) = self.surface_texture {
+            surface_texture.present();
+        }
+    }
+
+    pub fn width(&self) -> f32 {
+        self.texture.width() as f32
+    }
+
+    pub fn height(&self) -> f32 {
+        self.texture.height() as f32
+    }
+
+    /// The color attachment every frame pass draws into, resolving the multisampled target when MSAA is on.
+    pub fn color_attachment(
+        &self,
+        load: wgpu::LoadOp<wgpu::Color>,
+    ) -> wgpu::RenderPassColorAttachment<'_> {
+        let (view, resolve_target) = match &self.msaa_view {
+            Some(msaa_view) => (msaa_view, Some(&self.view)),
+            None => (&self.view, None),
+        };
+
+        wgpu::RenderPassColorAttachment {
+            view,
+            resolve_target,
+            ops: wgpu::Operations {
+                load,
+                store: wgpu::StoreOp::Store,
+            },
+            depth_slice: None,
+        }
+    }
+
+    /// The mask attachment every frame pass that draws with a stencil aware pipeline needs.
+    pub fn depth_stencil_attachment(&self) -> Option<wgpu::RenderPassDepthStencilAttachment<'_>> {
+        Some(self.stencil.attachment())
+    }
+}
+
+impl<S> Gamma<S> {
+    /// Make sure there is a frame to draw into, either from the window surface
+    /// or from the headless render target. Returns false when there is nothing to draw to.
+    pub(crate) fn acquire_frame(&mut self) -> bool {
+        if self.current_frame.is_some() {
+            return true;
+        }
+
+        let (surface_texture, texture) = if let Some(surface) = self.target_surface() {
+            match surface.get_current_texture() {
+                Ok(surface_texture) => {
+                    let texture = surface_texture.texture.clone();
+                    (Some(surface_texture), texture)
+                }
+                Err(e) => {
+                    eprintln!("Failed to get surface texture: {:?}", e);
+                    return false;
+                }
+            }
+        } else if let Some(texture) = self.headless_target.clone() {
+            (None, texture)
+        } else {
+            return false;
+        };
+
+        let (width, height) = (texture.width(), texture.height());
+        let Some(stencil) = self.frame_stencil(width, height) else {
+            return false;
+        };
+        let msaa_view = self.frame_msaa_view(width, height, texture.format());
+
+        self.current_frame = Some(Frame::new(surface_texture, texture, stencil, msaa_view));
+        true
+    }
+
+    /// Finish the current frame and present it to the window.
+    pub(crate) fn end_frame(&mut self) {
+        self.reset_frame_state();
+        self.finish_lighting();
+        self.draw_debug_layer();
+        self.draw_shader_errors();
+        self.finish_frame_stats();
+        self.record_frame();
+        self.present_frame();
+    }
+
+    /// Finish the frame of an extra window. Render statistics are only finished with the main window.
+    pub(crate) fn finish_frame(&mut self) {
+        self.reset_frame_state();
+        self.finish_lighting();
+        self.present_frame();
+    }
+
+    fn reset_frame_state(&mut self) {
+        // Clip rects, viewports and masking never carry over into the next frame or the engine's
+        // own overlays.
+        self.clip_stack.clear();
+        self.viewport = None;
+        self.writing_mask = false;
+        self.mask_mode = MaskMode::Disabled;
+    }
+
+    fn present_frame(&mut self) {
+        if let Some(frame) = self.current_frame.take() {
+            frame.present();
+        }
+    }
 }
@@ -0,0 +1,65 @@
+use crate::error::GammaError;
+use crate::gamma::Gamma;
+use crate::rendering::adapter::AdapterOptions;
+use crate::rendering::msaa::supported_sample_count;
+use crate::rendering::{MipmapPipeline, TexturePipeline};
+use wgpu::{TextureFormat, TextureUsages};
+
+/// The format of the offscreen render target. It is read back as RGBA without any swizzling.
+pub const HEADLESS_FORMAT: TextureFormat = TextureFormat::Rgba8UnormSrgb;
+
+/// Set up rendering without a window, drawing into an offscreen texture instead of a surface.
+///
+/// The software fallback adapter is preferred so results are the same on machines without a GPU,
+/// with any other adapter matching the builder's adapter options used when no fallback adapter is available.
+pub(crate) fn initialize_headless_rendering<S>(
+    gamma: &mut Gamma<S>,
+    width: u32,
+    height: u32,
+) -> Result<(), GammaError> {
+    let options = &gamma.adapter_options;
+    let instance = options.create_instance();
+
+    let fallback = AdapterOptions {
+        power_preference: wgpu::PowerPreference::LowPower,
+        force_fallback_adapter: true,
+        ..options.clone()
+    };
+
+    let adapter = match fallback.request_adapter(&instance, None) {
+        Ok(adapter) => adapter,
+        Err(_) => options.request_adapter(&instance, None)?,
+    };
+
+    let (device, queue) = options.request_device(&adapter)?;
+
+    let target = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Headless Render Target"),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: HEADLESS_FORMAT,
+        usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+        view_formats: &[],
+    });
+
+    let sample_count = supported_sample_count(&adapter, HEADLESS_FORMAT, gamma.msaa);
+    let texture_pipeline = TexturePipeline::new(&device, HEADLESS_FORMAT, sample_count);
+    let mipmap_pipeline = MipmapPipeline::new(&device);
+
+    gamma.instance = Some(instance);
+    gamma.device = Some(device);
+    gamma.queue = Some(queue);
+    gamma.adapter = Some(adapter);
+    gamma.headless_target = Some(target);
+    gamma.sample_count = sample_count;
+    gamma.texture_pipeline = Some(texture_pipeline);
+    gamma.mipmap_pipeline = Some(mipmap_pipeline);
+
+    Ok(())
+}
@@ -0,0 +1,368 @@
+use wgpu::{BindGroup, BindGroupLayout, Buffer, RenderPipeline, util::DeviceExt};
+
+use crate::{
+    gamma::Gamma,
+    rendering::{
+        Color, Texture,
+        stencil::{StencilMode, StencilPipelines},
+    },
+};
+
+/// One copy of a texture drawn by `draw_texture_instanced`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpriteInstance {
+    /// The left edge of the sprite in pixels.
+    pub x: f32,
+    /// The top edge of the sprite in pixels.
+    pub y: f32,
+    /// The drawn width in pixels. A negative width mirrors the sprite horizontally.
+    pub width: f32,
+    /// The drawn height in pixels. A negative height mirrors the sprite vertically.
+    pub height: f32,
+    /// Rotation around the center of the sprite in radians, turning clockwise.
+    pub rotation: f32,
+    /// The color the texture colors are multiplied by.
+    pub tint: Color,
+    /// The part of the texture to draw as x, y, width and height in texture pixels.
+    pub source: [f32; 4],
+}
+
+impl SpriteInstance {
+    /// An instance that draws the whole texture at its original size.
+    pub fn new(texture: &Texture, x: f32, y: f32) -> Self {
+        let (width, height) = (texture.width as f32, texture.height as f32);
+        Self {
+            x,
+            y,
+            width,
+            height,
+            rotation: 0.0,
+            tint: Color::WHITE,
+            source: [0.0, 0.0, width, height],
+        }
+    }
+
+    pub fn with_size(mut self, width: f32, height: f32) -> Self {
+        self.width = width;
+        self.height = height;
+        self
+    }
+
+    pub fn with_rotation(mut self, rotation: f32) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    pub fn with_tint(mut self, tint: impl Into<Color>) -> Self {
+        self.tint = tint.into();
+        self
+    }
+
+    /// Only draw part of the texture, such as a single frame of a sprite sheet.
+    /// The drawn size is set to the size of the source rect.
+    pub fn with_source(mut self, x: f32, y: f32, width: f32, height: f32) -> Self {
+        self.source = [x, y, width, height];
+        self.width = width;
+        self.height = height;
+        self
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct InstanceData {
+    position: [f32; 2],
+    size: [f32; 2],
+    rotation: f32,
+    tint: [f32; 4],
+    uv_rect: [f32; 4],
+}
+
+const INSTANCE_SHADER: &str = r#"
+struct Screen {
+    // The frame size and the offset of the viewport camera, both in world pixels.
+    size: vec4<f32>,
+}
+
+struct InstanceInput {
+    @location(0) position: vec2<f32>,
+    @location(1) size: vec2<f32>,
+    @location(2) rotation: f32,
+    @location(3) tint: vec4<f32>,
+    @location(4) uv_rect: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) tint: vec4<f32>,
+}
+
+@group(1) @binding(0)
+var<uniform> screen: Screen;
+
+@vertex
+fn vs_main(@location(5) corner: vec2<f32>, instance: InstanceInput) -> VertexOutput {
+    let local = (corner - vec2<f32>(0.5, 0.5)) * instance.size;
+    let c = cos(instance.rotation);
+    let s = sin(instance.rotation);
+    let rotated = vec2<f32>(local.x * c - local.y * s, local.x * s + local.y * c);
+    let pixel = instance.position + instance.size * 0.5 + rotated;
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(
+        (pixel.x + screen.size.z) / screen.size.x * 2.0 - 1.0,
+        1.0 - (pixel.y + screen.size.w) / screen.size.y * 2.0,
+        0.0,
+        1.0,
+    );
+    out.tex_coords = instance.uv_rect.xy + corner * instance.uv_rect.zw;
+    out.tint = instance.tint;
+    return out;
+}
+
+@group(0) @binding(0)
+var t_diffuse: texture_2d<f32>;
+@group(0) @binding(1)
+var s_diffuse: sampler;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(t_diffuse, s_diffuse, in.tex_coords) * in.tint;
+}
+
+@fragment
+fn fs_mask(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(t_diffuse, s_diffuse, in.tex_coords) * in.tint;
+    if (color.a < 0.5) {
+        discard;
+    }
+    return color;
+}
+"#;
+
+/// Draws many copies of one texture in a single draw call, with per instance data in a vertex buffer.
+pub struct InstancePipeline {
+    pub pipelines: StencilPipelines,
+    pub screen_buffer: Buffer,
+    pub screen_bind_group: BindGroup,
+    pub corner_buffer: Buffer,
+    pub index_buffer: Buffer,
+}
+
+impl InstancePipeline {
+    pub fn new(
+        device: &wgpu::Device,
+        surface_format: wgpu::TextureFormat,
+        texture_bind_group_layout: &BindGroupLayout,
+        sample_count: u32,
+    ) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Instance Shader"),
+            source: wgpu::ShaderSource::Wgsl(INSTANCE_SHADER.into()),
+        });
+
+        let screen_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Instance Screen Bind Group Layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+            });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Instance Pipeline Layout"),
+            bind_group_layouts: &[texture_bind_group_layout, &screen_bind_group_layout],
+            immediate_size: 0,
+        });
+
+        let pipelines = StencilPipelines::new(
+            device,
+            &pipeline_layout,
+            &shader,
+            &[
+                wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<InstanceData>() as wgpu::BufferAddress,
+                    step_mode: wgpu::VertexStepMode::Instance,
+                    attributes: &wgpu::vertex_attr_array![
+                        0 => Float32x2,
+                        1 => Float32x2,
+                        2 => Float32,
+                        3 => Float32x4,
+                        4 => Float32x4,
+                    ],
+                },
+                wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<[f32; 2]>() as wgpu::BufferAddress,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &wgpu::vertex_attr_array![5 => Float32x2],
+                },
+            ],
+            surface_format,
+            sample_count,
+            "Instance Render Pipeline",
+        );
+
+        let screen_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Instance Screen Buffer"),
+            size: std::mem::size_of::<[f32; 4]>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let screen_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Instance Screen Bind Group"),
+            layout: &screen_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: screen_buffer.as_entire_binding(),
+            }],
+        });
+
+        let corners: [[f32; 2]; 4] = [[0.0, 0.0], [1.0, 0.0], [0.0, 1.0], [1.0, 1.0]];
+        let corner_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Instance Corner Buffer"),
+            contents: bytemuck::cast_slice(&corners),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+
+        let indices: [u16; 6] = [0, 1, 2, 1, 3, 2];
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Instance Index Buffer"),
+            contents: bytemuck::cast_slice(&indices),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+
+        Self {
+            pipelines,
+            screen_buffer,
+            screen_bind_group,
+            corner_buffer,
+            index_buffer,
+        }
+    }
+
+    pub fn pipeline(&self, stencil: StencilMode) -> &RenderPipeline {
+        self.pipelines.get(stencil)
+    }
+}
+
+impl<S> Gamma<S> {
+    /// Draw many copies of the same texture with a single draw call.
+    ///
+    /// This is much faster than calling `draw_texture` in a loop when drawing thousands of sprites,
+    /// such as bullets or particles, that all share one texture or sprite sheet.
+    pub fn draw_texture_instanced(&mut self, texture: &Texture, instances: &[SpriteInstance]) {
+        if instances.is_empty() || !self.acquire_frame() {
+            return;
+        }
+
+        let (device, queue, texture_pipeline, frame) = match (
+            self.device.as_ref(),
+            self.queue.as_ref(),
+            self.texture_pipeline.as_ref(),
+            self.current_frame.as_ref(),
+        ) {
+            (Some(d), Some(q), Some(t), Some(f)) => (d, q, t, f),
+            _ => return,
+        };
+
+        let Some(scissor) = self.current_scissor(frame) else {
+            return;
+        };
+        let stencil = self.stencil_mode();
+        let view = self.view();
+
+        let sample_count = self.sample_count;
+        let pipeline = self.instance_pipeline.get_or_insert_with(|| {
+            InstancePipeline::new(
+                device,
+                frame.texture.format(),
+                &texture_pipeline.bind_group_layout,
+                sample_count,
+            )
+        });
+
+        let (texture_width, texture_height) = (texture.width as f32, texture.height as f32);
+        let data: Vec<InstanceData> = instances
+            .iter()
+            .map(|instance| InstanceData {
+                position: [instance.x, instance.y],
+                size: [instance.width, instance.height],
+                rotation: instance.rotation,
+                tint: instance.tint.to_linear_array(),
+                uv_rect: [
+                    instance.source[0] / texture_width,
+                    instance.source[1] / texture_height,
+                    instance.source[2] / texture_width,
+                    instance.source[3] / texture_height,
+                ],
+            })
+            .collect();
+
+        let screen = view.screen(frame);
+        queue.write_buffer(&pipeline.screen_buffer, 0, bytemuck::cast_slice(&screen));
+
+        let instance_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Sprite Instance Buffer"),
+            contents: bytemuck::cast_slice(&data),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Instance Texture Bind Group"),
+            layout: &texture_pipeline.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&texture.sampler),
+                },
+            ],
+        });
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Instance Render Encoder"),
+        });
+
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Instance Render Pass"),
+                color_attachments: &[Some(frame.color_attachment(wgpu::LoadOp::Load))],
+                depth_stencil_attachment: frame.depth_stencil_attachment(),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+                multiview_mask: None,
+            });
+
+            scissor.apply(&mut render_pass);
+            stencil.apply(&mut render_pass);
+            render_pass.set_pipeline(pipeline.pipeline(stencil));
+            render_pass.set_bind_group(0, &bind_group, &[]);
+            render_pass.set_bind_group(1, &pipeline.screen_bind_group, &[]);
+            render_pass.set_vertex_buffer(0, instance_buffer.slice(..));
+            render_pass.set_vertex_buffer(1, pipeline.corner_buffer.slice(..));
+            render_pass
+                .set_index_buffer(pipeline.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+            render_pass.draw_indexed(0..6, 0, 0..data.len() as u32);
+        }
+
+        queue.submit(std::iter::once(encoder.finish()));
+
+        self.frame_stats.batches += 1;
+        self.frame_stats.draw_calls += 1;
+        self.frame_stats.vertices += 4 * data.len() as u32;
+        self.frame_stats.texture_binds += 1;
+        self.frame_stats.buffer_uploads += 2;
+    }
+}
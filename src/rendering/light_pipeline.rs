@@ -0,0 +1,518 @@
+use wgpu::{
+    BindGroup, BindGroupLayout, Buffer, RenderPipeline, Sampler, TextureView, util::DeviceExt,
+};
+
+use crate::rendering::msaa::multisample_state;
+
+/// Both lighting targets use a plain 8 bit format so they work on every backend, including WebGL.
+pub const LIGHT_TARGET_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+
+/// The value the normal buffer is cleared to, a normal pointing straight out of the screen.
+pub const FLAT_NORMAL: wgpu::Color = wgpu::Color {
+    r: 0.5,
+    g: 0.5,
+    b: 1.0,
+    a: 1.0,
+};
+
+const NORMAL_SHADER: &str = r#"
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+    @location(1) tex_coords: vec2<f32>,
+    @location(2) normal_sign: vec2<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) normal_sign: vec2<f32>,
+}
+
+@vertex
+fn vs_main(input: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(input.position, 0.0, 1.0);
+    out.tex_coords = input.tex_coords;
+    out.normal_sign = input.normal_sign;
+    return out;
+}
+
+@group(0) @binding(0)
+var t_diffuse: texture_2d<f32>;
+@group(0) @binding(1)
+var s_diffuse: sampler;
+@group(0) @binding(2)
+var t_normal: texture_2d<f32>;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let alpha = textureSample(t_diffuse, s_diffuse, in.tex_coords).a;
+    var normal = textureSample(t_normal, s_diffuse, in.tex_coords).xyz * 2.0 - 1.0;
+
+    // Normal maps are "green up" while the screen is y down, and flipping a sprite mirrors its normals.
+    normal = vec3<f32>(normal.x * in.normal_sign.x, -normal.y * in.normal_sign.y, normal.z);
+    return vec4<f32>(normal * 0.5 + 0.5, alpha);
+}
+"#;
+
+const LIGHT_SHADER: &str = r#"
+// How far above the screen lights sit, relative to their radius.
+const LIGHT_HEIGHT: f32 = 0.3;
+
+struct Screen {
+    size: vec4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> screen: Screen;
+@group(0) @binding(1)
+var t_normals: texture_2d<f32>;
+
+struct LightInput {
+    @location(0) position: vec2<f32>,
+    @location(1) radius: f32,
+    @location(2) falloff: f32,
+    @location(3) color: vec4<f32>,
+    @location(4) direction: vec2<f32>,
+    @location(5) cone_cos: f32,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) center: vec2<f32>,
+    @location(1) radius: f32,
+    @location(2) falloff: f32,
+    @location(3) color: vec4<f32>,
+    @location(4) direction: vec2<f32>,
+    @location(5) cone_cos: f32,
+}
+
+// Each light is drawn as a quad that covers its radius.
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32, light: LightInput) -> VertexOutput {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(-1.0, 1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, 1.0),
+    );
+
+    let pixel = light.position + corners[index] * light.radius;
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(
+        pixel.x / screen.size.x * 2.0 - 1.0,
+        1.0 - pixel.y / screen.size.y * 2.0,
+        0.0,
+        1.0,
+    );
+    out.center = light.position;
+    out.radius = light.radius;
+    out.falloff = light.falloff;
+    out.color = light.color;
+    out.direction = light.direction;
+    out.cone_cos = light.cone_cos;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let to_pixel = in.clip_position.xy - in.center;
+    let distance = length(to_pixel);
+    let attenuation = pow(clamp(1.0 - distance / in.radius, 0.0, 1.0), in.falloff);
+
+    var cone = 1.0;
+    if (in.cone_cos > -1.0) {
+        let along = dot(to_pixel / max(distance, 0.0001), in.direction);
+        cone = smoothstep(in.cone_cos, mix(in.cone_cos, 1.0, 0.1), along);
+    }
+
+    let normal = textureLoad(t_normals, vec2<i32>(in.clip_position.xy), 0).xyz * 2.0 - 1.0;
+    let to_light = normalize(vec3<f32>(-to_pixel / in.radius, LIGHT_HEIGHT));
+    let diffuse = max(dot(normalize(normal), to_light), 0.0);
+
+    return vec4<f32>(in.color.rgb * attenuation * cone * diffuse, 1.0);
+}
+"#;
+
+const COMPOSITE_SHADER: &str = r#"
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    let x = f32((index << 1u) & 2u);
+    let y = f32(index & 2u);
+    return vec4<f32>(x * 2.0 - 1.0, 1.0 - y * 2.0, 0.0, 1.0);
+}
+
+@group(0) @binding(0)
+var t_light: texture_2d<f32>;
+
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    return vec4<f32>(textureLoad(t_light, vec2<i32>(position.xy), 0).rgb, 1.0);
+}
+"#;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct NormalVertex {
+    pub position: [f32; 2],
+    pub tex_coords: [f32; 2],
+    pub normal_sign: [f32; 2],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct LightInstance {
+    pub position: [f32; 2],
+    pub radius: f32,
+    pub falloff: f32,
+    pub color: [f32; 4],
+    pub direction: [f32; 2],
+    pub cone_cos: f32,
+    pub _padding: f32,
+}
+
+/// The pipelines used to light the scene: one writes sprite normals into the normal buffer,
+/// one accumulates lights into the light buffer, and one multiplies the light buffer over the frame.
+pub struct LightPipeline {
+    pub normal_bind_group_layout: BindGroupLayout,
+    pub normal_pipeline: RenderPipeline,
+    pub light_bind_group_layout: BindGroupLayout,
+    pub light_pipeline: RenderPipeline,
+    pub composite_bind_group_layout: BindGroupLayout,
+    pub composite_pipeline: RenderPipeline,
+    /// Used by sprites without a normal map so they still cover what is behind them.
+    pub flat_normal_view: TextureView,
+}
+
+/// The offscreen buffers lighting is rendered into, sized to match the frame.
+pub struct LightTargets {
+    pub width: u32,
+    pub height: u32,
+    pub normal_view: TextureView,
+    pub light_view: TextureView,
+    pub light_bind_group: BindGroup,
+    pub composite_bind_group: BindGroup,
+    _screen_buffer: Buffer,
+}
+
+impl LightPipeline {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        surface_format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> Self {
+        let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+
+        let normal_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Normal Bind Group Layout"),
+                entries: &[
+                    texture_entry(0),
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                    texture_entry(2),
+                ],
+            });
+
+        let light_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Light Bind Group Layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::VERTEX,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    texture_entry(1),
+                ],
+            });
+
+        let composite_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Light Composite Bind Group Layout"),
+                entries: &[texture_entry(0)],
+            });
+
+        let normal_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Normal Shader"),
+            source: wgpu::ShaderSource::Wgsl(NORMAL_SHADER.into()),
+        });
+
+        let light_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Light Shader"),
+            source: wgpu::ShaderSource::Wgsl(LIGHT_SHADER.into()),
+        });
+
+        let composite_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Light Composite Shader"),
+            source: wgpu::ShaderSource::Wgsl(COMPOSITE_SHADER.into()),
+        });
+
+        let normal_pipeline = create_pipeline(
+            device,
+            "Normal Render Pipeline",
+            &normal_bind_group_layout,
+            &normal_shader,
+            &[wgpu::VertexBufferLayout {
+                array_stride: std::mem::size_of::<NormalVertex>() as wgpu::BufferAddress,
+                step_mode: wgpu::VertexStepMode::Vertex,
+                attributes: &wgpu::vertex_attr_array![
+                    0 => Float32x2,
+                    1 => Float32x2,
+                    2 => Float32x2,
+                ],
+            }],
+            LIGHT_TARGET_FORMAT,
+            1,
+            wgpu::BlendState::ALPHA_BLENDING,
+        );
+
+        let light_pipeline = create_pipeline(
+            device,
+            "Light Render Pipeline",
+            &light_bind_group_layout,
+            &light_shader,
+            &[wgpu::VertexBufferLayout {
+                array_stride: std::mem::size_of::<LightInstance>() as wgpu::BufferAddress,
+                step_mode: wgpu::VertexStepMode::Instance,
+                attributes: &wgpu::vertex_attr_array![
+                    0 => Float32x2,
+                    1 => Float32,
+                    2 => Float32,
+                    3 => Float32x4,
+                    4 => Float32x2,
+                    5 => Float32,
+                ],
+            }],
+            LIGHT_TARGET_FORMAT,
+            1,
+            // Lights add up on top of the ambient color.
+            wgpu::BlendState {
+                color: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::One,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                alpha: wgpu::BlendComponent::OVER,
+            },
+        );
+
+        let composite_pipeline = create_pipeline(
+            device,
+            "Light Composite Pipeline",
+            &composite_bind_group_layout,
+            &composite_shader,
+            &[],
+            surface_format,
+            sample_count,
+            // Multiply the frame by the light buffer and leave its alpha alone.
+            wgpu::BlendState {
+                color: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::Dst,
+                    dst_factor: wgpu::BlendFactor::Zero,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                alpha: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::Zero,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                },
+            },
+        );
+
+        let flat_normal = device.create_texture_with_data(
+            queue,
+            &wgpu::TextureDescriptor {
+                label: Some("Flat Normal Texture"),
+                size: wgpu::Extent3d {
+                    width: 1,
+                    height: 1,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba8Unorm,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            },
+            wgpu::util::TextureDataOrder::LayerMajor,
+            &[128, 128, 255, 255],
+        );
+        let flat_normal_view = flat_normal.create_view(&wgpu::TextureViewDescriptor::default());
+
+        Self {
+            normal_bind_group_layout,
+            normal_pipeline,
+            light_bind_group_layout,
+            light_pipeline,
+            composite_bind_group_layout,
+            composite_pipeline,
+            flat_normal_view,
+        }
+    }
+
+    pub fn create_targets(&self, device: &wgpu::Device, width: u32, height: u32) -> LightTargets {
+        let create_target = |label| {
+            device
+                .create_texture(&wgpu::TextureDescriptor {
+                    label: Some(label),
+                    size: wgpu::Extent3d {
+                        width,
+                        height,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: LIGHT_TARGET_FORMAT,
+                    usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                        | wgpu::TextureUsages::TEXTURE_BINDING,
+                    view_formats: &[],
+                })
+                .create_view(&wgpu::TextureViewDescriptor::default())
+        };
+
+        let normal_view = create_target("Normal Buffer");
+        let light_view = create_target("Light Buffer");
+
+        let screen: [f32; 4] = [width as f32, height as f32, 0.0, 0.0];
+        let screen_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Light Screen Buffer"),
+            contents: bytemuck::cast_slice(&screen),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
+        let light_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Light Bind Group"),
+            layout: &self.light_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: screen_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&normal_view),
+                },
+            ],
+        });
+
+        let composite_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Light Composite Bind Group"),
+            layout: &self.composite_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&light_view),
+            }],
+        });
+
+        LightTargets {
+            width,
+            height,
+            normal_view,
+            light_view,
+            light_bind_group,
+            composite_bind_group,
+            _screen_buffer: screen_buffer,
+        }
+    }
+
+    pub fn normal_bind_group(
+        &self,
+        device: &wgpu::Device,
+        diffuse: &TextureView,
+        sampler: &Sampler,
+        normal: Option<&TextureView>,
+    ) -> BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Normal Bind Group"),
+            layout: &self.normal_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(diffuse),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(
+                        normal.unwrap_or(&self.flat_normal_view),
+                    ),
+                },
+            ],
+        })
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn create_pipeline(
+    device: &wgpu::Device,
+    label: &str,
+    bind_group_layout: &BindGroupLayout,
+    shader: &wgpu::ShaderModule,
+    buffers: &[wgpu::VertexBufferLayout],
+    format: wgpu::TextureFormat,
+    sample_count: u32,
+    blend: wgpu::BlendState,
+) -> RenderPipeline {
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some(label),
+        bind_group_layouts: &[bind_group_layout],
+        immediate_size: 0,
+    });
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some(label),
+        layout: Some(&pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: Some("vs_main"),
+            buffers,
+            compilation_options: Default::default(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: Some("fs_main"),
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: Some(blend),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+            compilation_options: Default::default(),
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            ..Default::default()
+        },
+        depth_stencil: None,
+        multisample: multisample_state(sample_count),
+        multiview_mask: None,
+        cache: None,
+    })
+}
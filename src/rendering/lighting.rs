@@ -0,0 +1,424 @@
+use wgpu::util::DeviceExt;
+
+use crate::{
+    gamma::Gamma,
+    rendering::{
+        Color, Flip, Texture,
+        clip::Scissor,
+        light_pipeline::{FLAT_NORMAL, LightInstance, LightPipeline, LightTargets, NormalVertex},
+        texture::SpriteVertex,
+    },
+};
+
+/// The shape of the area a light shines on.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LightKind {
+    /// Shines equally in every direction.
+    Point,
+    /// Shines in a cone. `direction` is the angle of the cone in radians, where 0 points right and
+    /// positive angles turn clockwise, and `cone_angle` is the angle from its center to its edge.
+    Spot { direction: f32, cone_angle: f32 },
+}
+
+/// A dynamic light that brightens the scene around it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Light {
+    pub x: f32,
+    pub y: f32,
+    /// The color of the light. Its alpha is ignored.
+    pub color: Color,
+    pub intensity: f32,
+    /// The distance in pixels where the light fades out completely.
+    pub radius: f32,
+    /// How sharply the light fades towards its radius. 1.0 fades linearly, higher values fade faster.
+    pub falloff: f32,
+    pub kind: LightKind,
+}
+
+impl Light {
+    /// A white point light.
+    pub fn point(x: f32, y: f32, radius: f32) -> Self {
+        Self {
+            x,
+            y,
+            color: Color::WHITE,
+            intensity: 1.0,
+            radius,
+            falloff: 2.0,
+            kind: LightKind::Point,
+        }
+    }
+
+    /// A white spot light.
+    pub fn spot(x: f32, y: f32, radius: f32, direction: f32, cone_angle: f32) -> Self {
+        Self {
+            kind: LightKind::Spot {
+                direction,
+                cone_angle,
+            },
+            ..Self::point(x, y, radius)
+        }
+    }
+
+    pub fn with_color(mut self, color: impl Into<Color>) -> Self {
+        self.color = color.into();
+        self
+    }
+
+    pub fn with_intensity(mut self, intensity: f32) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    pub fn with_falloff(mut self, falloff: f32) -> Self {
+        self.falloff = falloff;
+        self
+    }
+
+    fn instance(&self) -> LightInstance {
+        let (direction, cone_cos) = match self.kind {
+            LightKind::Point => ([1.0, 0.0], -2.0),
+            LightKind::Spot {
+                direction,
+                cone_angle,
+            } => ([direction.cos(), direction.sin()], cone_angle.cos()),
+        };
+
+        let color = self.color.to_linear();
+        LightInstance {
+            position: [self.x, self.y],
+            radius: self.radius.max(f32::EPSILON),
+            falloff: self.falloff,
+            color: [
+                color.r * self.intensity,
+                color.g * self.intensity,
+                color.b * self.intensity,
+                1.0,
+            ],
+            direction,
+            cone_cos,
+            _padding: 0.0,
+        }
+    }
+}
+
+pub(crate) struct LightingState {
+    pub enabled: bool,
+    /// Set while lighting still has to be applied to the current frame.
+    pub pending: bool,
+    pub ambient: Color,
+    pub lights: Vec<Light>,
+    /// Set once anything has written into the normal buffer this frame.
+    pub normals_written: bool,
+    pub pipeline: Option<LightPipeline>,
+    pub targets: Option<LightTargets>,
+}
+
+impl Default for LightingState {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            pending: false,
+            ambient: Color::WHITE,
+            lights: Vec::new(),
+            normals_written: false,
+            pipeline: None,
+            targets: None,
+        }
+    }
+}
+
+impl<S> Gamma<S> {
+    /// Turn on lighting and set the color of the light that reaches everything in the scene.
+    ///
+    /// Once lighting is on, the frame is darkened to the ambient color and brightened by the lights
+    /// added with `add_light`. Lighting is applied at the end of the frame, or earlier with
+    /// `apply_lighting` so that things such as the UI can be drawn unlit on top.
+    pub fn set_ambient_light(&mut self, color: impl Into<Color>) {
+        self.lighting.enabled = true;
+        self.lighting.pending = true;
+        self.lighting.ambient = color.into();
+    }
+
+    /// Turn lighting off, drawing everything at full brightness again.
+    pub fn disable_lighting(&mut self) {
+        self.lighting.enabled = false;
+        self.lighting.pending = false;
+        self.lighting.lights.clear();
+    }
+
+    /// Add a light to the current frame.
+    pub fn add_light(&mut self, mut light: Light) {
+        // Lights are applied to the whole frame at once, so they are kept in frame pixels.
+        let view = self.view();
+        (light.x, light.y) = view.point(light.x, light.y);
+        light.radius = view.length(light.radius);
+        self.lighting.lights.push(light);
+    }
+
+    /// Light everything drawn so far this frame with the ambient color and the lights added so far.
+    pub fn apply_lighting(&mut self) {
+        if !self.lighting.pending || !self.acquire_frame() || !self.ensure_light_targets() {
+            return;
+        }
+
+        let (device, queue, frame, pipeline, targets) = match (
+            self.device.as_ref(),
+            self.queue.as_ref(),
+            self.current_frame.as_ref(),
+            self.lighting.pipeline.as_ref(),
+            self.lighting.targets.as_ref(),
+        ) {
+            (Some(d), Some(q), Some(f), Some(p), Some(t)) => (d, q, f, p, t),
+            _ => return,
+        };
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Lighting Encoder"),
+        });
+
+        if !self.lighting.normals_written {
+            clear_normals(&mut encoder, targets);
+        }
+
+        let instances: Vec<LightInstance> =
+            self.lighting.lights.iter().map(Light::instance).collect();
+        let Color { r, g, b, .. } = self.lighting.ambient.to_linear();
+
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Light Accumulation Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &targets.light_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color {
+                            r: r as f64,
+                            g: g as f64,
+                            b: b as f64,
+                            a: 1.0,
+                        }),
+                        store: wgpu::StoreOp::Store,
+                    },
+                    depth_slice: None,
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+                multiview_mask: None,
+            });
+
+            if !instances.is_empty() {
+                let instance_buffer =
+                    device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                        label: Some("Light Instance Buffer"),
+                        contents: bytemuck::cast_slice(&instances),
+                        usage: wgpu::BufferUsages::VERTEX,
+                    });
+
+                render_pass.set_pipeline(&pipeline.light_pipeline);
+                render_pass.set_bind_group(0, &targets.light_bind_group, &[]);
+                render_pass.set_vertex_buffer(0, instance_buffer.slice(..));
+                render_pass.draw(0..6, 0..instances.len() as u32);
+
+                self.frame_stats.draw_calls += 1;
+                self.frame_stats.vertices += 6 * instances.len() as u32;
+                self.frame_stats.texture_binds += 1;
+                self.frame_stats.buffer_uploads += 1;
+            }
+        }
+
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Light Composite Pass"),
+                color_attachments: &[Some(frame.color_attachment(wgpu::LoadOp::Load))],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+                multiview_mask: None,
+            });
+
+            render_pass.set_pipeline(&pipeline.composite_pipeline);
+            render_pass.set_bind_group(0, &targets.composite_bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+
+        queue.submit(std::iter::once(encoder.finish()));
+
+        self.frame_stats.batches += 2;
+        self.frame_stats.draw_calls += 1;
+        self.frame_stats.vertices += 3;
+        self.frame_stats.texture_binds += 1;
+
+        // Everything drawn after this point is unlit.
+        self.lighting.pending = false;
+    }
+
+    /// Apply lighting if the user has not already done so, then reset it for the next frame.
+    pub(crate) fn finish_lighting(&mut self) {
+        if self.lighting.pending {
+            self.apply_lighting();
+        }
+
+        self.lighting.lights.clear();
+        self.lighting.normals_written = false;
+        self.lighting.pending = self.lighting.enabled;
+    }
+
+    /// Write the normals of a sprite that was just drawn into the normal buffer.
+    ///
+    /// Sprites without a normal map only need to be written once a normal mapped sprite has
+    /// been drawn, to cover up the parts of it they are drawn over.
+    pub(crate) fn draw_sprite_normals(
+        &mut self,
+        texture: &Texture,
+        normal_map: Option<&Texture>,
+        vertices: &[SpriteVertex; 4],
+        flip: Flip,
+        scissor: Scissor,
+    ) {
+        if !self.lighting.pending
+            || self.writing_mask
+            || (normal_map.is_none() && !self.lighting.normals_written)
+            || !self.ensure_light_targets()
+        {
+            return;
+        }
+
+        let (device, queue, pipeline, targets) = match (
+            self.device.as_ref(),
+            self.queue.as_ref(),
+            self.lighting.pipeline.as_ref(),
+            self.lighting.targets.as_ref(),
+        ) {
+            (Some(d), Some(q), Some(p), Some(t)) => (d, q, p, t),
+            _ => return,
+        };
+
+        let normal_sign = match flip {
+            Flip::None => [1.0, 1.0],
+            Flip::Horizontal => [-1.0, 1.0],
+            Flip::Vertical => [1.0, -1.0],
+            Flip::Both => [-1.0, -1.0],
+        };
+
+        let normal_vertices = vertices.map(|vertex| NormalVertex {
+            position: vertex.position,
+            tex_coords: vertex.tex_coords,
+            normal_sign,
+        });
+        let indices: [u16; 6] = [0, 1, 2, 1, 3, 2];
+
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Normal Vertex Buffer"),
+            contents: bytemuck::cast_slice(&normal_vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Normal Index Buffer"),
+            contents: bytemuck::cast_slice(&indices),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+
+        let bind_group = pipeline.normal_bind_group(
+            device,
+            &texture.view,
+            &texture.sampler,
+            normal_map.map(|normal_map| &normal_map.view),
+        );
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Normal Render Encoder"),
+        });
+
+        if !self.lighting.normals_written {
+            clear_normals(&mut encoder, targets);
+        }
+
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Normal Render Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &targets.normal_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                    depth_slice: None,
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+                multiview_mask: None,
+            });
+
+            scissor.apply(&mut render_pass);
+            render_pass.set_pipeline(&pipeline.normal_pipeline);
+            render_pass.set_bind_group(0, &bind_group, &[]);
+            render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+            render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+            render_pass.draw_indexed(0..6, 0, 0..1);
+        }
+
+        queue.submit(std::iter::once(encoder.finish()));
+        self.lighting.normals_written = true;
+
+        self.frame_stats.batches += 1;
+        self.frame_stats.draw_calls += 1;
+        self.frame_stats.vertices += 4;
+        self.frame_stats.texture_binds += 1;
+        self.frame_stats.buffer_uploads += 2;
+    }
+
+    /// Create the lighting pipeline and make sure the light buffers match the frame size.
+    fn ensure_light_targets(&mut self) -> bool {
+        let sample_count = self.sample_count;
+        let (device, queue, frame) = match (
+            self.device.as_ref(),
+            self.queue.as_ref(),
+            self.current_frame.as_ref(),
+        ) {
+            (Some(d), Some(q), Some(f)) => (d, q, f),
+            _ => return false,
+        };
+
+        let pipeline = self.lighting.pipeline.get_or_insert_with(|| {
+            LightPipeline::new(device, queue, frame.texture.format(), sample_count)
+        });
+
+        let (width, height) = (frame.texture.width(), frame.texture.height());
+        let matches_frame = self
+            .lighting
+            .targets
+            .as_ref()
+            .is_some_and(|targets| targets.width == width && targets.height == height);
+
+        if !matches_frame {
+            self.lighting.targets = Some(pipeline.create_targets(device, width, height));
+            self.lighting.normals_written = false;
+        }
+
+        true
+    }
+}
+
+fn clear_normals(encoder: &mut wgpu::CommandEncoder, targets: &LightTargets) {
+    encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some("Normal Clear Pass"),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view: &targets.normal_view,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(FLAT_NORMAL),
+                store: wgpu::StoreOp::Store,
+            },
+            depth_slice: None,
+        })],
+        depth_stencil_attachment: None,
+        timestamp_writes: None,
+        occlusion_query_set: None,
+        multiview_mask: None,
+    });
+}
@@ -0,0 +1,487 @@
+use wgpu::{BindGroup, BindGroupLayout, Buffer, RenderPipeline, util::DeviceExt};
+
+use crate::{
+    gamma::Gamma,
+    rendering::{
+        Color, Texture,
+        stencil::{StencilMode, StencilPipelines},
+    },
+};
+
+/// One corner of a mesh triangle.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct MeshVertex {
+    /// The position in pixels, in the same space as sprites.
+    pub position: [f32; 2],
+    /// The texture coordinates from 0.0 to 1.0. Ignored when the mesh is drawn without a texture.
+    pub uv: [f32; 2],
+    /// The color the texture color is multiplied by.
+    pub color: Color,
+}
+
+impl MeshVertex {
+    /// A vertex with a color and no texture coordinates.
+    pub fn colored(x: f32, y: f32, color: impl Into<Color>) -> Self {
+        Self {
+            position: [x, y],
+            uv: [0.0, 0.0],
+            color: color.into(),
+        }
+    }
+
+    /// A white vertex that shows the texture unchanged.
+    pub fn textured(x: f32, y: f32, u: f32, v: f32) -> Self {
+        Self {
+            position: [x, y],
+            uv: [u, v],
+            color: Color::WHITE,
+        }
+    }
+}
+
+/// Where the vertices of a mesh live between draws.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MeshUsage {
+    /// Uploaded once and kept on the GPU. Changes are uploaded on the next draw.
+    Static,
+    /// Uploaded every time the mesh is drawn. Best for geometry that changes every frame.
+    Dynamic,
+}
+
+/// Custom 2D geometry made of indexed triangles, drawn with `draw_mesh`.
+pub struct Mesh {
+    vertices: Vec<MeshVertex>,
+    indices: Vec<u32>,
+    usage: MeshUsage,
+    buffers: Option<MeshBuffers>,
+    dirty: bool,
+}
+
+/// The GPU copy of a static mesh.
+struct MeshBuffers {
+    vertex_buffer: Buffer,
+    index_buffer: Buffer,
+    vertex_capacity: usize,
+    index_capacity: usize,
+}
+
+impl Mesh {
+    /// A mesh made of triangles, with every three indices naming the vertices of one triangle.
+    pub fn new(vertices: Vec<MeshVertex>, indices: Vec<u32>, usage: MeshUsage) -> Self {
+        Self {
+            vertices,
+            indices,
+            usage,
+            buffers: None,
+            dirty: true,
+        }
+    }
+
+    /// A mesh whose vertices are used in order, three for each triangle.
+    pub fn from_triangles(vertices: Vec<MeshVertex>, usage: MeshUsage) -> Self {
+        let indices = (0..vertices.len() as u32).collect();
+        Self::new(vertices, indices, usage)
+    }
+
+    pub fn usage(&self) -> MeshUsage {
+        self.usage
+    }
+
+    pub fn vertices(&self) -> &[MeshVertex] {
+        &self.vertices
+    }
+
+    pub fn indices(&self) -> &[u32] {
+        &self.indices
+    }
+
+    /// Mutable access to the vertices. A static mesh is uploaded again on the next draw.
+    pub fn vertices_mut(&mut self) -> &mut Vec<MeshVertex> {
+        self.dirty = true;
+        &mut self.vertices
+    }
+
+    /// Mutable access to the indices. A static mesh is uploaded again on the next draw.
+    pub fn indices_mut(&mut self) -> &mut Vec<u32> {
+        self.dirty = true;
+        &mut self.indices
+    }
+
+    /// Replace the whole geometry.
+    pub fn set(&mut self, vertices: Vec<MeshVertex>, indices: Vec<u32>) {
+        self.vertices = vertices;
+        self.indices = indices;
+        self.dirty = true;
+    }
+
+    /// Remove every vertex and index, keeping the allocations for reuse.
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+        self.indices.clear();
+        self.dirty = true;
+    }
+
+    /// Add vertices and the triangles between them, with indices relative to the first new vertex.
+    pub fn extend(&mut self, vertices: &[MeshVertex], indices: &[u32]) {
+        let base = self.vertices.len() as u32;
+        self.vertices.extend_from_slice(vertices);
+        self.indices
+            .extend(indices.iter().map(|index| base + index));
+        self.dirty = true;
+    }
+}
+
+const MESH_SHADER: &str = r#"
+struct Screen {
+    // The frame size and the offset of the viewport camera, both in world pixels.
+    size: vec4<f32>,
+}
+
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+    @location(1) uv: vec2<f32>,
+    @location(2) color: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec4<f32>,
+}
+
+@group(1) @binding(0)
+var<uniform> screen: Screen;
+
+// Vertex colors are sRGB, while blending happens in linear light.
+fn srgb_to_linear(color: vec3<f32>) -> vec3<f32> {
+    let low = color / 12.92;
+    let high = pow((color + 0.055) / 1.055, vec3<f32>(2.4));
+    return select(high, low, color <= vec3<f32>(0.04045));
+}
+
+@vertex
+fn vs_main(input: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(
+        (input.position.x + screen.size.z) / screen.size.x * 2.0 - 1.0,
+        1.0 - (input.position.y + screen.size.w) / screen.size.y * 2.0,
+        0.0,
+        1.0,
+    );
+    out.uv = input.uv;
+    out.color = vec4<f32>(srgb_to_linear(input.color.rgb), input.color.a);
+    return out;
+}
+
+@group(0) @binding(0)
+var t_diffuse: texture_2d<f32>;
+@group(0) @binding(1)
+var s_diffuse: sampler;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(t_diffuse, s_diffuse, in.uv) * in.color;
+}
+
+@fragment
+fn fs_mask(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(t_diffuse, s_diffuse, in.uv) * in.color;
+    if (color.a < 0.5) {
+        discard;
+    }
+    return color;
+}
+"#;
+
+/// Draws indexed triangles with per vertex colors, optionally textured.
+pub struct MeshPipeline {
+    pub pipelines: StencilPipelines,
+    pub screen_buffer: Buffer,
+    pub screen_bind_group: BindGroup,
+    /// Bound when a mesh is drawn without a texture, so only the vertex colors show.
+    pub white_bind_group: BindGroup,
+}
+
+impl MeshPipeline {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        surface_format: wgpu::TextureFormat,
+        texture_bind_group_layout: &BindGroupLayout,
+        sample_count: u32,
+    ) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Mesh Shader"),
+            source: wgpu::ShaderSource::Wgsl(MESH_SHADER.into()),
+        });
+
+        let screen_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Mesh Screen Bind Group Layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+            });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Mesh Pipeline Layout"),
+            bind_group_layouts: &[texture_bind_group_layout, &screen_bind_group_layout],
+            immediate_size: 0,
+        });
+
+        let pipelines = StencilPipelines::new(
+            device,
+            &pipeline_layout,
+            &shader,
+            &[wgpu::VertexBufferLayout {
+                array_stride: std::mem::size_of::<MeshVertex>() as wgpu::BufferAddress,
+                step_mode: wgpu::VertexStepMode::Vertex,
+                attributes: &wgpu::vertex_attr_array![
+                    0 => Float32x2,
+                    1 => Float32x2,
+                    2 => Float32x4,
+                ],
+            }],
+            surface_format,
+            sample_count,
+            "Mesh Render Pipeline",
+        );
+
+        let screen_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Mesh Screen Buffer"),
+            size: std::mem::size_of::<[f32; 4]>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let screen_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Mesh Screen Bind Group"),
+            layout: &screen_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: screen_buffer.as_entire_binding(),
+            }],
+        });
+
+        let white_texture = device.create_texture_with_data(
+            queue,
+            &wgpu::TextureDescriptor {
+                label: Some("Mesh White Texture"),
+                size: wgpu::Extent3d {
+                    width: 1,
+                    height: 1,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba8UnormSrgb,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            },
+            wgpu::util::TextureDataOrder::LayerMajor,
+            &[255, 255, 255, 255],
+        );
+        let white_view = white_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let white_sampler = device.create_sampler(&wgpu::SamplerDescriptor::default());
+
+        let white_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Mesh White Bind Group"),
+            layout: texture_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&white_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&white_sampler),
+                },
+            ],
+        });
+
+        Self {
+            pipelines,
+            screen_buffer,
+            screen_bind_group,
+            white_bind_group,
+        }
+    }
+
+    pub fn pipeline(&self, stencil: StencilMode) -> &RenderPipeline {
+        self.pipelines.get(stencil)
+    }
+}
+
+impl<S> Gamma<S> {
+    /// Draw a mesh, textured when a texture is given and with only its vertex colors otherwise.
+    ///
+    /// Vertex positions are in pixels like every other draw call, so meshes line up with sprites.
+    pub fn draw_mesh(&mut self, mesh: &mut Mesh, texture: Option<&Texture>) {
+        if mesh.indices.is_empty() || mesh.vertices.is_empty() || !self.acquire_frame() {
+            return;
+        }
+
+        let (device, queue, texture_pipeline, frame) = match (
+            self.device.as_ref(),
+            self.queue.as_ref(),
+            self.texture_pipeline.as_ref(),
+            self.current_frame.as_ref(),
+        ) {
+            (Some(d), Some(q), Some(t), Some(f)) => (d, q, t, f),
+            _ => return,
+        };
+
+        let Some(scissor) = self.current_scissor(frame) else {
+            return;
+        };
+        let stencil = self.stencil_mode();
+        let view = self.view();
+
+        let sample_count = self.sample_count;
+        let pipeline = self.mesh_pipeline.get_or_insert_with(|| {
+            MeshPipeline::new(
+                device,
+                queue,
+                frame.texture.format(),
+                &texture_pipeline.bind_group_layout,
+                sample_count,
+            )
+        });
+
+        let screen = view.screen(frame);
+        queue.write_buffer(&pipeline.screen_buffer, 0, bytemuck::cast_slice(&screen));
+
+        let mut buffer_uploads = 0;
+        let temporary_buffers;
+        let (vertex_buffer, index_buffer) = match mesh.usage {
+            MeshUsage::Static => {
+                if mesh.dirty {
+                    buffer_uploads += upload_static_mesh(device, queue, mesh);
+                }
+                let buffers = mesh.buffers.as_ref().unwrap();
+                (&buffers.vertex_buffer, &buffers.index_buffer)
+            }
+            MeshUsage::Dynamic => {
+                temporary_buffers = (
+                    device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                        label: Some("Mesh Vertex Buffer"),
+                        contents: bytemuck::cast_slice(&mesh.vertices),
+                        usage: wgpu::BufferUsages::VERTEX,
+                    }),
+                    device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                        label: Some("Mesh Index Buffer"),
+                        contents: bytemuck::cast_slice(&mesh.indices),
+                        usage: wgpu::BufferUsages::INDEX,
+                    }),
+                );
+                buffer_uploads += 2;
+                (&temporary_buffers.0, &temporary_buffers.1)
+            }
+        };
+
+        let texture_bind_group = texture.map(|texture| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Mesh Texture Bind Group"),
+                layout: &texture_pipeline.bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&texture.view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&texture.sampler),
+                    },
+                ],
+            })
+        });
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Mesh Render Encoder"),
+        });
+
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Mesh Render Pass"),
+                color_attachments: &[Some(frame.color_attachment(wgpu::LoadOp::Load))],
+                depth_stencil_attachment: frame.depth_stencil_attachment(),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+                multiview_mask: None,
+            });
+
+            scissor.apply(&mut render_pass);
+            stencil.apply(&mut render_pass);
+            render_pass.set_pipeline(pipeline.pipeline(stencil));
+            render_pass.set_bind_group(
+                0,
+                texture_bind_group
+                    .as_ref()
+                    .unwrap_or(&pipeline.white_bind_group),
+                &[],
+            );
+            render_pass.set_bind_group(1, &pipeline.screen_bind_group, &[]);
+            render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+            render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+            render_pass.draw_indexed(0..mesh.indices.len() as u32, 0, 0..1);
+        }
+
+        queue.submit(std::iter::once(encoder.finish()));
+
+        self.frame_stats.batches += 1;
+        self.frame_stats.draw_calls += 1;
+        self.frame_stats.vertices += mesh.vertices.len() as u32;
+        self.frame_stats.texture_binds += 1;
+        self.frame_stats.buffer_uploads += buffer_uploads;
+    }
+}
+
+/// Copy a static mesh to its GPU buffers, growing them when the mesh no longer fits.
+/// Returns the number of buffers written.
+fn upload_static_mesh(device: &wgpu::Device, queue: &wgpu::Queue, mesh: &mut Mesh) -> u32 {
+    let fits = mesh.buffers.as_ref().is_some_and(|buffers| {
+        buffers.vertex_capacity >= mesh.vertices.len()
+            && buffers.index_capacity >= mesh.indices.len()
+    });
+
+    if fits {
+        let buffers = mesh.buffers.as_ref().unwrap();
+        queue.write_buffer(
+            &buffers.vertex_buffer,
+            0,
+            bytemuck::cast_slice(&mesh.vertices),
+        );
+        queue.write_buffer(
+            &buffers.index_buffer,
+            0,
+            bytemuck::cast_slice(&mesh.indices),
+        );
+    } else {
+        mesh.buffers = Some(MeshBuffers {
+            vertex_buffer: device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Static Mesh Vertex Buffer"),
+                contents: bytemuck::cast_slice(&mesh.vertices),
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            }),
+            index_buffer: device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Static Mesh Index Buffer"),
+                contents: bytemuck::cast_slice(&mesh.indices),
+                usage: wgpu::BufferUsages::INDEX | wgpu::BufferUsages::COPY_DST,
+            }),
+            vertex_capacity: mesh.vertices.len(),
+            index_capacity: mesh.indices.len(),
+        });
+    }
+
+    mesh.dirty = false;
+    2
+}
@@ -1,9 +1,51 @@
+pub(crate) mod adapter;
 pub(crate) mod clear;
+pub(crate) mod clip;
+pub(crate) mod color;
 pub(crate) mod context;
+pub(crate) mod debug;
 pub(crate) mod frame;
+pub(crate) mod headless;
+pub(crate) mod instancing;
+pub(crate) mod light_pipeline;
+pub(crate) mod lighting;
+pub(crate) mod mesh;
+pub(crate) mod mipmap;
+pub(crate) mod msaa;
 pub(crate) mod pipeline;
+pub(crate) mod pixel_buffer;
+pub(crate) mod polyline;
+pub(crate) mod readback;
+pub(crate) mod recording;
+pub(crate) mod registry;
+pub(crate) mod screenshot;
+pub(crate) mod sdf_text;
+pub(crate) mod shader;
+pub(crate) mod sprite_effect;
+pub(crate) mod stats;
+pub(crate) mod stencil;
+pub(crate) mod text;
 pub(crate) mod texture;
+pub(crate) mod viewport;
+pub(crate) mod windows;
 
-pub use frame::Frame;
+pub use color::Color;
+pub use context::PresentMode;
+pub use debug::DebugDraw;
+pub use instancing::SpriteInstance;
+pub use lighting::{Light, LightKind};
+pub use mesh::{Mesh, MeshUsage, MeshVertex};
+pub use mipmap::MipmapPipeline;
 pub use pipeline::TexturePipeline;
+pub use pixel_buffer::PixelBuffer;
+pub use polyline::{LineCap, LineJoin, LineStyle, PolylinePoint, Trail};
+pub use recording::{RecordingFormat, RecordingOptions};
+pub use registry::TextureId;
+pub use sdf_text::{SdfFont, TextStyle};
+pub use shader::Shader;
+pub use sprite_effect::SpriteEffect;
+pub use stats::RenderStats;
+pub use stencil::MaskMode;
 pub use texture::{Flip, Texture};
+pub use viewport::{Camera, Viewport};
+pub use windows::WindowId;
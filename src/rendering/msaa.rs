@@ -0,0 +1,84 @@
+use wgpu::{Adapter, TextureFormat, TextureFormatFeatureFlags, TextureView};
+
+use crate::{gamma::Gamma, rendering::stencil::STENCIL_FORMAT};
+
+/// The highest sample count up to `requested` that both the color and mask formats support.
+/// Falls back to 1, which turns multisampling off.
+pub(crate) fn supported_sample_count(
+    adapter: &Adapter,
+    color_format: TextureFormat,
+    requested: u32,
+) -> u32 {
+    let color = adapter.get_texture_format_features(color_format).flags;
+    let stencil = adapter.get_texture_format_features(STENCIL_FORMAT).flags;
+
+    [16, 8, 4, 2]
+        .into_iter()
+        .filter(|&count| count <= requested)
+        .find(|&count| {
+            color.contains(TextureFormatFeatureFlags::MULTISAMPLE_RESOLVE)
+                && color.sample_count_supported(count)
+                && stencil.sample_count_supported(count)
+        })
+        .unwrap_or(1)
+}
+
+/// The multisample state every pipeline drawing into the frame must use.
+pub(crate) fn multisample_state(sample_count: u32) -> wgpu::MultisampleState {
+    wgpu::MultisampleState {
+        count: sample_count,
+        mask: !0,
+        alpha_to_coverage_enabled: false,
+    }
+}
+
+impl<S> Gamma<S> {
+    /// The number of samples per pixel used for anti-aliasing, 1 when it is off.
+    ///
+    /// This can be lower than the count given to `with_msaa` when the adapter does not support it.
+    pub fn msaa_samples(&self) -> u32 {
+        self.sample_count
+    }
+
+    /// The multisampled color target drawn into before being resolved into the frame.
+    /// Returns `None` when multisampling is off.
+    pub(crate) fn frame_msaa_view(
+        &mut self,
+        width: u32,
+        height: u32,
+        format: TextureFormat,
+    ) -> Option<TextureView> {
+        if self.sample_count <= 1 {
+            return None;
+        }
+
+        let device = self.device.as_ref()?;
+
+        let matches = self.msaa_texture.as_ref().is_some_and(|texture| {
+            texture.width() == width && texture.height() == height && texture.format() == format
+        });
+
+        if !matches {
+            self.msaa_texture = Some(device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("Multisampled Frame Texture"),
+                size: wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: self.sample_count,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                view_formats: &[],
+            }));
+        }
+
+        Some(
+            self.msaa_texture
+                .as_ref()?
+                .create_view(&wgpu::TextureViewDescriptor::default()),
+        )
+    }
+}
@@ -1,8 +1,17 @@
 use wgpu::{BindGroupLayout, RenderPipeline};
 
+use crate::rendering::stencil::{StencilMode, StencilPipelines};
+
+/// Blends straight alpha colors into the frame while keeping the frame's alpha the coverage of
+/// everything drawn, which is what a transparent window needs to blend with the desktop.
+pub(crate) const FRAME_BLEND: wgpu::BlendState = wgpu::BlendState {
+    color: wgpu::BlendState::ALPHA_BLENDING.color,
+    alpha: wgpu::BlendComponent::OVER,
+};
+
 pub struct TexturePipeline {
     pub bind_group_layout: BindGroupLayout,
-    pub pipeline: RenderPipeline,
+    pub pipelines: StencilPipelines,
 }
 
 const TEXTURE_SHADER: &str = r#"
@@ -31,15 +40,42 @@ var s_diffuse: sampler;
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return sprite_color(in);
+}
+
+// Used when drawing into the mask, so only the visible parts of a sprite end up in it.
+@fragment
+fn fs_mask(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = sprite_color(in);
+    if (color.a < 0.5) {
+        discard;
+    }
+    return color;
+}
+"#;
+
+/// The color of a sprite without any effects, completing `TEXTURE_SHADER`.
+const SPRITE_COLOR: &str = r#"
+fn sprite_color(in: VertexOutput) -> vec4<f32> {
     return textureSample(t_diffuse, s_diffuse, in.tex_coords);
 }
 "#;
 
+/// `TEXTURE_SHADER` completed with a `sprite_color` function. Sprite effects use this to change
+/// the color of a sprite while drawing it the same way.
+pub(crate) fn texture_shader(sprite_color: &str) -> String {
+    format!("{}\n{}", TEXTURE_SHADER, sprite_color)
+}
+
 impl TexturePipeline {
-    pub fn new(device: &wgpu::Device, surface_format: wgpu::TextureFormat) -> Self {
+    pub fn new(
+        device: &wgpu::Device,
+        surface_format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> Self {
         let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label: Some("Texture Shader"),
-            source: wgpu::ShaderSource::Wgsl(TEXTURE_SHADER.into()),
+            source: wgpu::ShaderSource::Wgsl(texture_shader(SPRITE_COLOR).into()),
         });
 
         let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
@@ -77,53 +113,38 @@ impl TexturePipeline {
             tex_coords: [f32; 2],
         }
 
-        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Texture Render Pipeline"),
-            layout: Some(&pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: Some("vs_main"),
-                buffers: &[wgpu::VertexBufferLayout {
-                    array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
-                    step_mode: wgpu::VertexStepMode::Vertex,
-                    attributes: &[
-                        wgpu::VertexAttribute {
-                            offset: 0,
-                            shader_location: 0,
-                            format: wgpu::VertexFormat::Float32x2,
-                        },
-                        wgpu::VertexAttribute {
-                            offset: std::mem::size_of::<[f32; 2]>() as wgpu::BufferAddress,
-                            shader_location: 1,
-                            format: wgpu::VertexFormat::Float32x2,
-                        },
-                    ],
-                }],
-                compilation_options: Default::default(),
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: Some("fs_main"),
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: surface_format,
-                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-                compilation_options: Default::default(),
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                ..Default::default()
-            },
-            depth_stencil: None,
-            multisample: wgpu::MultisampleState::default(),
-            multiview_mask: None,
-            cache: None,
-        });
+        let pipelines = StencilPipelines::new(
+            device,
+            &pipeline_layout,
+            &shader,
+            &[wgpu::VertexBufferLayout {
+                array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
+                step_mode: wgpu::VertexStepMode::Vertex,
+                attributes: &[
+                    wgpu::VertexAttribute {
+                        offset: 0,
+                        shader_location: 0,
+                        format: wgpu::VertexFormat::Float32x2,
+                    },
+                    wgpu::VertexAttribute {
+                        offset: std::mem::size_of::<[f32; 2]>() as wgpu::BufferAddress,
+                        shader_location: 1,
+                        format: wgpu::VertexFormat::Float32x2,
+                    },
+                ],
+            }],
+            surface_format,
+            sample_count,
+            "Texture Render Pipeline",
+        );
 
         Self {
             bind_group_layout,
-            pipeline,
+            pipelines,
         }
     }
+
+    pub fn pipeline(&self, stencil: StencilMode) -> &RenderPipeline {
+        self.pipelines.get(stencil)
+    }
 }
@@ -0,0 +1,193 @@
+use crate::{
+    error::GammaError,
+    gamma::Gamma,
+    rendering::{Color, Flip, Texture},
+};
+
+/// A texture with a copy of its pixels kept on the CPU for drawing into directly.
+///
+/// Changes are tracked as a single dirty rectangle and uploaded to the GPU the next time
+/// the buffer is drawn with `draw_pixel_buffer` or synced with `sync_pixel_buffer`.
+/// Useful for destructible terrain, fog of war and paint tools.
+pub struct PixelBuffer {
+    texture: Texture,
+    pixels: Vec<u8>,
+    dirty: Option<DirtyRect>,
+}
+
+/// The area changed since the last upload, with exclusive right and bottom edges.
+#[derive(Debug, Clone, Copy)]
+struct DirtyRect {
+    left: u32,
+    top: u32,
+    right: u32,
+    bottom: u32,
+}
+
+impl PixelBuffer {
+    pub fn width(&self) -> u32 {
+        self.texture.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.texture.height
+    }
+
+    /// The texture holding the pixels from the last sync.
+    pub fn texture(&self) -> &Texture {
+        &self.texture
+    }
+
+    /// The RGBA color of a pixel, or `None` when it is outside the buffer.
+    pub fn get_pixel(&self, x: u32, y: u32) -> Option<[u8; 4]> {
+        if x >= self.width() || y >= self.height() {
+            return None;
+        }
+
+        let i = self.index(x, y);
+        Some([
+            self.pixels[i],
+            self.pixels[i + 1],
+            self.pixels[i + 2],
+            self.pixels[i + 3],
+        ])
+    }
+
+    /// Set the RGBA color of a pixel. Pixels outside the buffer are ignored.
+    pub fn set_pixel(&mut self, x: u32, y: u32, color: impl Into<Color>) {
+        if x >= self.width() || y >= self.height() {
+            return;
+        }
+
+        let i = self.index(x, y);
+        self.pixels[i..i + 4].copy_from_slice(&color.into().to_rgba8());
+        self.mark_dirty(x, y, 1, 1);
+    }
+
+    /// Fill a rectangle with an RGBA color. The part outside the buffer is ignored.
+    pub fn fill_rect(&mut self, x: u32, y: u32, width: u32, height: u32, color: impl Into<Color>) {
+        let color = color.into().to_rgba8();
+        let right = x.saturating_add(width).min(self.width());
+        let bottom = y.saturating_add(height).min(self.height());
+        if x >= right || y >= bottom {
+            return;
+        }
+
+        for row in y..bottom {
+            for column in x..right {
+                let i = self.index(column, row);
+                self.pixels[i..i + 4].copy_from_slice(&color);
+            }
+        }
+
+        self.mark_dirty(x, y, right - x, bottom - y);
+    }
+
+    /// Fill the whole buffer with an RGBA color.
+    pub fn clear(&mut self, color: impl Into<Color>) {
+        self.fill_rect(0, 0, self.width(), self.height(), color);
+    }
+
+    /// The raw RGBA pixels, four bytes per pixel in rows from top to bottom.
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    /// Mutable access to the raw RGBA pixels. The whole buffer is uploaded on the next sync.
+    pub fn pixels_mut(&mut self) -> &mut [u8] {
+        self.mark_dirty(0, 0, self.width(), self.height());
+        &mut self.pixels
+    }
+
+    /// Flag a rectangle as changed so it is uploaded on the next sync.
+    pub fn mark_dirty(&mut self, x: u32, y: u32, width: u32, height: u32) {
+        let rect = DirtyRect {
+            left: x.min(self.width()),
+            top: y.min(self.height()),
+            right: x.saturating_add(width).min(self.width()),
+            bottom: y.saturating_add(height).min(self.height()),
+        };
+        if rect.left >= rect.right || rect.top >= rect.bottom {
+            return;
+        }
+
+        self.dirty = Some(match self.dirty {
+            Some(dirty) => DirtyRect {
+                left: dirty.left.min(rect.left),
+                top: dirty.top.min(rect.top),
+                right: dirty.right.max(rect.right),
+                bottom: dirty.bottom.max(rect.bottom),
+            },
+            None => rect,
+        });
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        (y as usize * self.width() as usize + x as usize) * 4
+    }
+}
+
+impl<S> Gamma<S> {
+    /// Create a fully transparent pixel buffer.
+    pub fn create_pixel_buffer(&self, width: u32, height: u32) -> Result<PixelBuffer, GammaError> {
+        let texture = self.create_texture(width, height)?;
+
+        Ok(PixelBuffer {
+            texture,
+            pixels: vec![0; width as usize * height as usize * 4],
+            dirty: None,
+        })
+    }
+
+    /// Upload the pixels changed since the last sync to the GPU.
+    pub fn sync_pixel_buffer(&mut self, buffer: &mut PixelBuffer) -> Result<(), GammaError> {
+        let Some(dirty) = buffer.dirty else {
+            return Ok(());
+        };
+
+        let queue = self
+            .queue
+            .as_ref()
+            .ok_or(GammaError::NotInitialized("Queue"))?;
+
+        // Upload straight from the full buffer by starting at the dirty corner and
+        // stepping over whole rows, so no pixels need to be copied first.
+        queue.write_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture: &buffer.texture.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: dirty.left,
+                    y: dirty.top,
+                    z: 0,
+                },
+                aspect: wgpu::TextureAspect::All,
+            },
+            &buffer.pixels,
+            wgpu::TexelCopyBufferLayout {
+                offset: buffer.index(dirty.left, dirty.top) as wgpu::BufferAddress,
+                bytes_per_row: Some(4 * buffer.width()),
+                rows_per_image: None,
+            },
+            wgpu::Extent3d {
+                width: dirty.right - dirty.left,
+                height: dirty.bottom - dirty.top,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        buffer.dirty = None;
+        self.frame_stats.buffer_uploads += 1;
+
+        Ok(())
+    }
+
+    /// Upload any changed pixels and draw the buffer at a position.
+    pub fn draw_pixel_buffer(&mut self, buffer: &mut PixelBuffer, x: f32, y: f32, flip: Flip) {
+        if self.sync_pixel_buffer(buffer).is_err() {
+            return;
+        }
+
+        self.draw_texture(&buffer.texture, x, y, flip);
+    }
+}
@@ -0,0 +1,382 @@
+use std::{collections::VecDeque, f32::consts::PI, time::Duration};
+
+use crate::{
+    gamma::Gamma,
+    rendering::{Color, Mesh, MeshUsage, MeshVertex},
+};
+
+/// How two segments of a polyline are connected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineJoin {
+    /// Extend the outer edges until they meet in a sharp corner.
+    /// Falls back to a bevel when the corner would be longer than the miter limit.
+    #[default]
+    Miter,
+    /// Round the outer corner off with an arc.
+    Round,
+    /// Cut the outer corner off with a straight edge.
+    Bevel,
+}
+
+/// How the two ends of a polyline are drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineCap {
+    /// End exactly at the first and last point.
+    #[default]
+    Butt,
+    /// Extend past the end by half the line width with a half circle.
+    Round,
+    /// Extend past the end by half the line width with a square.
+    Square,
+}
+
+/// How a polyline is joined and capped.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LineStyle {
+    pub join: LineJoin,
+    pub cap: LineCap,
+    /// The longest a miter corner may be, as a multiple of half the line width.
+    pub miter_limit: f32,
+}
+
+impl Default for LineStyle {
+    fn default() -> Self {
+        Self {
+            join: LineJoin::Miter,
+            cap: LineCap::Butt,
+            miter_limit: 4.0,
+        }
+    }
+}
+
+impl LineStyle {
+    pub fn with_join(mut self, join: LineJoin) -> Self {
+        self.join = join;
+        self
+    }
+
+    pub fn with_cap(mut self, cap: LineCap) -> Self {
+        self.cap = cap;
+        self
+    }
+
+    pub fn with_miter_limit(mut self, miter_limit: f32) -> Self {
+        self.miter_limit = miter_limit;
+        self
+    }
+}
+
+/// One point of a polyline, with the line width and color at that point.
+/// Width and color blend smoothly along each segment.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PolylinePoint {
+    pub x: f32,
+    pub y: f32,
+    /// The full width of the line in pixels.
+    pub width: f32,
+    pub color: Color,
+}
+
+impl PolylinePoint {
+    pub fn new(x: f32, y: f32, width: f32, color: impl Into<Color>) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            color: color.into(),
+        }
+    }
+}
+
+/// Records positions over time and draws them as a line that thins and fades out behind them,
+/// such as the trail of a sword swipe or a fast projectile.
+#[derive(Debug, Clone)]
+pub struct Trail {
+    points: VecDeque<TrailPoint>,
+    lifetime: Duration,
+    width: f32,
+    color: Color,
+    min_distance: f32,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct TrailPoint {
+    x: f32,
+    y: f32,
+    age: Duration,
+}
+
+impl Trail {
+    /// A trail whose points last `lifetime`, starting at `width` and `color` at the newest point.
+    pub fn new(lifetime: Duration, width: f32, color: impl Into<Color>) -> Self {
+        Self {
+            points: VecDeque::new(),
+            lifetime,
+            width,
+            color: color.into(),
+            min_distance: 2.0,
+        }
+    }
+
+    /// Skip new positions closer than this many pixels to the last recorded one.
+    pub fn with_min_distance(mut self, min_distance: f32) -> Self {
+        self.min_distance = min_distance;
+        self
+    }
+
+    /// Record a position. Usually called once per frame with the position of whatever leaves the trail.
+    pub fn push(&mut self, x: f32, y: f32) {
+        if let Some(last) = self.points.back() {
+            let (dx, dy) = (x - last.x, y - last.y);
+            if (dx * dx + dy * dy).sqrt() < self.min_distance {
+                return;
+            }
+        }
+
+        self.points.push_back(TrailPoint {
+            x,
+            y,
+            age: Duration::ZERO,
+        });
+    }
+
+    /// Age every point and drop the ones older than the lifetime.
+    pub fn update(&mut self, delta: Duration) {
+        for point in &mut self.points {
+            point.age += delta;
+        }
+
+        while self
+            .points
+            .front()
+            .is_some_and(|point| point.age >= self.lifetime)
+        {
+            self.points.pop_front();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.points.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    /// The recorded points from oldest to newest, thinner and more transparent the older they are.
+    pub fn polyline_points(&self) -> Vec<PolylinePoint> {
+        let lifetime = self.lifetime.as_secs_f32().max(f32::EPSILON);
+
+        self.points
+            .iter()
+            .map(|point| {
+                let life = (1.0 - point.age.as_secs_f32() / lifetime).clamp(0.0, 1.0);
+                let color = self.color.with_alpha(self.color.a * life);
+                PolylinePoint::new(point.x, point.y, self.width * life, color)
+            })
+            .collect()
+    }
+}
+
+impl<S> Gamma<S> {
+    /// Draw a thick line through the points, joined and capped according to the style.
+    ///
+    /// Segments overlap slightly at the joins, so translucent lines look a little denser there.
+    pub fn draw_polyline(&mut self, points: &[PolylinePoint], style: LineStyle) {
+        let mut mesh = Mesh::new(Vec::new(), Vec::new(), MeshUsage::Dynamic);
+        tessellate_polyline(&mut mesh, points, style);
+        self.draw_mesh(&mut mesh, None);
+    }
+
+    /// Draw a trail with round joins, so it stays smooth through sharp turns.
+    pub fn draw_trail(&mut self, trail: &Trail) {
+        let style = LineStyle::default()
+            .with_join(LineJoin::Round)
+            .with_cap(LineCap::Round);
+        self.draw_polyline(&trail.polyline_points(), style);
+    }
+}
+
+/// Turn a polyline into triangles, adding them to the mesh.
+pub(crate) fn tessellate_polyline(mesh: &mut Mesh, points: &[PolylinePoint], style: LineStyle) {
+    // Repeated points have no direction, so they are dropped before anything else.
+    let mut points: Vec<PolylinePoint> = points.to_vec();
+    points.dedup_by(|b, a| (b.x - a.x).abs() < 1e-4 && (b.y - a.y).abs() < 1e-4);
+    if points.len() < 2 {
+        return;
+    }
+
+    for segment in points.windows(2) {
+        let (start, end) = (segment[0], segment[1]);
+        let normal = normal(start, end);
+        let (start_half, end_half) = (start.width * 0.5, end.width * 0.5);
+
+        mesh.extend(
+            &[
+                vertex(start, normal, start_half, start.color),
+                vertex(start, normal, -start_half, start.color),
+                vertex(end, normal, end_half, end.color),
+                vertex(end, normal, -end_half, end.color),
+            ],
+            &[0, 1, 2, 1, 3, 2],
+        );
+    }
+
+    for corner in points.windows(3) {
+        add_join(mesh, corner[0], corner[1], corner[2], style);
+    }
+
+    let last = points.len() - 1;
+    add_cap(mesh, points[1], points[0], style.cap);
+    add_cap(mesh, points[last - 1], points[last], style.cap);
+}
+
+/// Fill the gap on the outer side of the corner at `point`.
+fn add_join(
+    mesh: &mut Mesh,
+    previous: PolylinePoint,
+    point: PolylinePoint,
+    next: PolylinePoint,
+    style: LineStyle,
+) {
+    let incoming = normal(previous, point);
+    let outgoing = normal(point, next);
+    let turn = cross(direction(previous, point), direction(point, next));
+    if turn.abs() < 1e-6 {
+        return;
+    }
+
+    // The outer side is opposite to the direction the line turns.
+    let side = if turn > 0.0 { -1.0 } else { 1.0 };
+    let half = point.width * 0.5;
+    let from = scale(incoming, side);
+    let to = scale(outgoing, side);
+
+    match style.join {
+        LineJoin::Bevel => add_bevel(mesh, point, from, to, half),
+        LineJoin::Round => add_arc(mesh, point, from, to, half),
+        LineJoin::Miter => {
+            let middle = normalize([from[0] + to[0], from[1] + to[1]]);
+            let cos_half_angle = dot(middle, from);
+            let length = 1.0 / cos_half_angle.max(1e-6);
+
+            if length > style.miter_limit {
+                add_bevel(mesh, point, from, to, half);
+            } else {
+                mesh.extend(
+                    &[
+                        vertex(point, [0.0, 0.0], 0.0, point.color),
+                        vertex(point, from, half, point.color),
+                        vertex(point, middle, half * length, point.color),
+                        vertex(point, to, half, point.color),
+                    ],
+                    &[0, 1, 2, 0, 2, 3],
+                );
+            }
+        }
+    }
+}
+
+fn add_cap(mesh: &mut Mesh, inner: PolylinePoint, end: PolylinePoint, cap: LineCap) {
+    let forward = direction(inner, end);
+    let normal = normal(inner, end);
+    let half = end.width * 0.5;
+
+    match cap {
+        LineCap::Butt => {}
+        LineCap::Square => {
+            let extended = PolylinePoint {
+                x: end.x + forward[0] * half,
+                y: end.y + forward[1] * half,
+                ..end
+            };
+            mesh.extend(
+                &[
+                    vertex(end, normal, half, end.color),
+                    vertex(end, normal, -half, end.color),
+                    vertex(extended, normal, half, end.color),
+                    vertex(extended, normal, -half, end.color),
+                ],
+                &[0, 1, 2, 1, 3, 2],
+            );
+        }
+        LineCap::Round => {
+            add_arc(mesh, end, normal, forward, half);
+            add_arc(mesh, end, forward, scale(normal, -1.0), half);
+        }
+    }
+}
+
+fn add_bevel(mesh: &mut Mesh, point: PolylinePoint, from: [f32; 2], to: [f32; 2], half: f32) {
+    mesh.extend(
+        &[
+            vertex(point, [0.0, 0.0], 0.0, point.color),
+            vertex(point, from, half, point.color),
+            vertex(point, to, half, point.color),
+        ],
+        &[0, 1, 2],
+    );
+}
+
+/// A fan around `point` sweeping the shorter way from one unit offset to the other.
+fn add_arc(mesh: &mut Mesh, point: PolylinePoint, from: [f32; 2], to: [f32; 2], half: f32) {
+    let start = from[1].atan2(from[0]);
+    let mut sweep = to[1].atan2(to[0]) - start;
+    if sweep > PI {
+        sweep -= 2.0 * PI;
+    } else if sweep < -PI {
+        sweep += 2.0 * PI;
+    }
+
+    // Bigger arcs need more steps to stay round.
+    let steps = ((sweep.abs() * half.sqrt()).ceil() as u32).clamp(1, 64);
+
+    let mut vertices = vec![vertex(point, [0.0, 0.0], 0.0, point.color)];
+    let mut indices = Vec::with_capacity(steps as usize * 3);
+    for step in 0..=steps {
+        let angle = start + sweep * step as f32 / steps as f32;
+        vertices.push(vertex(point, [angle.cos(), angle.sin()], half, point.color));
+        if step > 0 {
+            indices.extend([0, step, step + 1]);
+        }
+    }
+
+    mesh.extend(&vertices, &indices);
+}
+
+fn vertex(point: PolylinePoint, offset: [f32; 2], distance: f32, color: Color) -> MeshVertex {
+    MeshVertex::colored(
+        point.x + offset[0] * distance,
+        point.y + offset[1] * distance,
+        color,
+    )
+}
+
+fn direction(from: PolylinePoint, to: PolylinePoint) -> [f32; 2] {
+    normalize([to.x - from.x, to.y - from.y])
+}
+
+fn normal(from: PolylinePoint, to: PolylinePoint) -> [f32; 2] {
+    let [x, y] = direction(from, to);
+    [-y, x]
+}
+
+fn normalize(v: [f32; 2]) -> [f32; 2] {
+    let length = (v[0] * v[0] + v[1] * v[1]).sqrt();
+    if length == 0.0 {
+        return [0.0, 0.0];
+    }
+    [v[0] / length, v[1] / length]
+}
+
+fn scale(v: [f32; 2], factor: f32) -> [f32; 2] {
+    [v[0] * factor, v[1] * factor]
+}
+
+fn dot(a: [f32; 2], b: [f32; 2]) -> f32 {
+    a[0] * b[0] + a[1] * b[1]
+}
+
+fn cross(a: [f32; 2], b: [f32; 2]) -> f32 {
+    a[0] * b[1] - a[1] * b[0]
+}
@@ -0,0 +1,157 @@
+use image::RgbaImage;
+use wgpu::{Buffer, TextureFormat};
+
+use crate::error::GammaError;
+
+/// A GPU buffer holding a copy of a texture that can be mapped and read on the CPU.
+pub struct ReadbackBuffer {
+    pub buffer: Buffer,
+    pub width: u32,
+    pub height: u32,
+    pub padded_bytes_per_row: u32,
+    pub format: TextureFormat,
+}
+
+impl ReadbackBuffer {
+    /// Record a copy of the whole texture into a new mappable buffer.
+    ///
+    /// Rows are padded to `COPY_BYTES_PER_ROW_ALIGNMENT`, which is undone again
+    /// in [`ReadbackBuffer::to_image`].
+    pub fn copy_from_texture(
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        texture: &wgpu::Texture,
+    ) -> Result<Self, GammaError> {
+        let readback = Self::for_texture(device, texture)?;
+        readback.copy(encoder, texture);
+        Ok(readback)
+    }
+
+    /// Create a buffer large enough to hold a copy of the texture, without copying anything yet.
+    pub fn for_texture(device: &wgpu::Device, texture: &wgpu::Texture) -> Result<Self, GammaError> {
+        let format = texture.format();
+        if !is_supported_format(format) {
+            return Err(GammaError::Render(format!(
+                "Cannot read back texture format {:?}",
+                format
+            )));
+        }
+        if !texture.usage().contains(wgpu::TextureUsages::COPY_SRC) {
+            return Err(GammaError::Surface(
+                "This surface does not allow reading frames back".to_string(),
+            ));
+        }
+
+        let width = texture.width();
+        let height = texture.height();
+        let unpadded_bytes_per_row = 4 * width;
+        let padded_bytes_per_row = unpadded_bytes_per_row
+            .div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+            * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Readback Buffer"),
+            size: (padded_bytes_per_row * height) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        Ok(Self {
+            buffer,
+            width,
+            height,
+            padded_bytes_per_row,
+            format,
+        })
+    }
+
+    /// Whether the buffer has the right size and format to hold a copy of the texture.
+    pub fn fits(&self, texture: &wgpu::Texture) -> bool {
+        self.width == texture.width()
+            && self.height == texture.height()
+            && self.format == texture.format()
+    }
+
+    /// Record a copy of the whole texture into the buffer, which must be unmapped and fit it.
+    pub fn copy(&self, encoder: &mut wgpu::CommandEncoder, texture: &wgpu::Texture) {
+        encoder.copy_texture_to_buffer(
+            wgpu::TexelCopyTextureInfo {
+                texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::TexelCopyBufferInfo {
+                buffer: &self.buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(self.padded_bytes_per_row),
+                    rows_per_image: Some(self.height),
+                },
+            },
+            wgpu::Extent3d {
+                width: self.width,
+                height: self.height,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+
+    /// Map the buffer and block until the GPU has finished writing into it.
+    pub fn map_blocking(&self, device: &wgpu::Device) -> Result<(), GammaError> {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        self.buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                let _ = sender.send(result);
+            });
+
+        device
+            .poll(wgpu::PollType::wait_indefinitely())
+            .map_err(|e| GammaError::Render(format!("Failed to wait for readback: {}", e)))?;
+
+        receiver
+            .recv()
+            .map_err(|e| GammaError::Render(format!("Readback was never mapped: {}", e)))?
+            .map_err(|e| GammaError::Render(format!("Failed to map readback buffer: {}", e)))
+    }
+
+    /// Convert the mapped buffer into a tightly packed RGBA image and unmap it.
+    pub fn to_image(&self) -> Result<RgbaImage, GammaError> {
+        let unpadded_bytes_per_row = (4 * self.width) as usize;
+        let mut pixels = Vec::with_capacity(unpadded_bytes_per_row * self.height as usize);
+
+        {
+            let data = self.buffer.slice(..).get_mapped_range();
+            for row in data.chunks(self.padded_bytes_per_row as usize) {
+                pixels.extend_from_slice(&row[..unpadded_bytes_per_row]);
+            }
+        }
+        self.buffer.unmap();
+
+        if is_bgra(self.format) {
+            for pixel in pixels.chunks_exact_mut(4) {
+                pixel.swap(0, 2);
+            }
+        }
+
+        RgbaImage::from_raw(self.width, self.height, pixels).ok_or_else(|| {
+            GammaError::Render("Readback buffer did not match the image size".to_string())
+        })
+    }
+}
+
+fn is_bgra(format: TextureFormat) -> bool {
+    matches!(
+        format,
+        TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb
+    )
+}
+
+fn is_supported_format(format: TextureFormat) -> bool {
+    is_bgra(format)
+        || matches!(
+            format,
+            TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb
+        )
+}
@@ -0,0 +1,609 @@
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{BufWriter, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver, SyncSender, TryRecvError, TrySendError},
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use image::{
+    Delay, ImageFormat, RgbaImage,
+    codecs::gif::{GifEncoder, Repeat},
+    imageops::{self, FilterType},
+};
+
+use crate::{error::GammaError, gamma::Gamma, rendering::readback::ReadbackBuffer};
+
+/// How many frames can be on their way back from the GPU at once.
+/// Frames are skipped rather than waited for when all of them are in use.
+const READBACK_RING_SIZE: usize = 3;
+
+/// How many read back frames can wait for the encoder before new ones are skipped.
+const ENCODER_QUEUE_SIZE: usize = 8;
+
+/// Frame times are rounded, so a frame this close to the next capture time still counts as on time.
+const CAPTURE_TOLERANCE: Duration = Duration::from_micros(500);
+
+/// APNG needs the frame count up front, so a placeholder is written and fixed once recording ends.
+const APNG_PLACEHOLDER_FRAMES: u32 = i32::MAX as u32;
+
+/// The file format a recording is saved in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordingFormat {
+    /// An animated GIF, small and widely supported but limited to 256 colors per frame.
+    Gif,
+    /// An animated PNG with full color.
+    Apng,
+    /// A directory of numbered PNG files, starting with `frame_00000.png`.
+    PngSequence,
+}
+
+/// What to record and where to save it, passed to `start_recording`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordingOptions {
+    /// The file to write, or the directory to fill for `RecordingFormat::PngSequence`.
+    pub path: PathBuf,
+    pub format: RecordingFormat,
+    /// Recording stops by itself after this much game time.
+    pub duration: Duration,
+    /// Record at most this many frames per second, or every presented frame when `None`.
+    pub fps: Option<u32>,
+}
+
+impl RecordingOptions {
+    pub fn new(path: impl Into<PathBuf>, format: RecordingFormat, duration: Duration) -> Self {
+        Self {
+            path: path.into(),
+            format,
+            duration,
+            fps: None,
+        }
+    }
+
+    /// Limit the frame rate of the recording, which keeps GIFs much smaller.
+    pub fn with_fps(mut self, fps: u32) -> Self {
+        self.fps = Some(fps);
+        self
+    }
+}
+
+/// A recording in progress: frames read back from the GPU and sent to the encoder thread.
+pub(crate) struct Recorder {
+    capturing: bool,
+    started: bool,
+    /// Wait for the GPU and the encoder instead of skipping frames, used when running headless.
+    wait_for_frames: bool,
+    duration: Duration,
+    frame_interval: Option<Duration>,
+    /// Game time since the first recorded frame.
+    elapsed: Duration,
+    next_capture: Duration,
+    frames: Option<SyncSender<RecordedFrame>>,
+    encoder: Option<JoinHandle<Result<PathBuf, GammaError>>>,
+    /// Copies waiting for the GPU, oldest first.
+    pending: VecDeque<PendingReadback>,
+    /// Buffers that finished a copy and can be used again.
+    free: Vec<ReadbackBuffer>,
+    error: Option<GammaError>,
+}
+
+struct PendingReadback {
+    readback: ReadbackBuffer,
+    timestamp: Duration,
+    mapped: Receiver<Result<(), wgpu::BufferAsyncError>>,
+}
+
+struct RecordedFrame {
+    image: RgbaImage,
+    timestamp: Duration,
+}
+
+impl Recorder {
+    /// Start reading the texture back into a free buffer, or skip the frame when none is free.
+    fn capture(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        texture: &wgpu::Texture,
+        timestamp: Duration,
+    ) {
+        // Buffers of another size are left over from before the window was resized.
+        self.free.retain(|readback| readback.fits(texture));
+
+        let readback = match self.free.pop() {
+            Some(readback) => readback,
+            None if self.pending.len() < READBACK_RING_SIZE => {
+                match ReadbackBuffer::for_texture(device, texture) {
+                    Ok(readback) => readback,
+                    Err(e) => return self.fail(e),
+                }
+            }
+            None if self.wait_for_frames => match self.wait_for_oldest(device) {
+                Some(readback) => readback,
+                None => return,
+            },
+            None => return,
+        };
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Recording Readback Encoder"),
+        });
+        readback.copy(&mut encoder, texture);
+        queue.submit(std::iter::once(encoder.finish()));
+
+        let (sender, receiver) = mpsc::channel();
+        readback
+            .buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                let _ = sender.send(result);
+            });
+
+        self.pending.push_back(PendingReadback {
+            readback,
+            timestamp,
+            mapped: receiver,
+        });
+    }
+
+    /// Hand every copy the GPU has finished to the encoder, without waiting for the others.
+    fn collect(&mut self) {
+        while let Some(pending) = self.pending.front() {
+            let mapped = match pending.mapped.try_recv() {
+                Ok(result) => result,
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => Err(wgpu::BufferAsyncError),
+            };
+            let pending = self.pending.pop_front().unwrap();
+
+            if let Err(e) = mapped {
+                self.fail(GammaError::Render(format!(
+                    "Failed to map a recorded frame: {}",
+                    e
+                )));
+                continue;
+            }
+
+            match pending.readback.to_image() {
+                Ok(image) => self.send(RecordedFrame {
+                    image,
+                    timestamp: pending.timestamp,
+                }),
+                Err(e) => self.fail(e),
+            }
+            self.free.push(pending.readback);
+        }
+    }
+
+    fn send(&mut self, frame: RecordedFrame) {
+        let Some(frames) = self.frames.as_ref() else {
+            return;
+        };
+
+        if self.wait_for_frames {
+            if frames.send(frame).is_err() {
+                self.capturing = false;
+            }
+            return;
+        }
+
+        match frames.try_send(frame) {
+            // The encoder is behind, so this frame is left out rather than stalling the game.
+            Ok(()) | Err(TrySendError::Full(_)) => {}
+            Err(TrySendError::Disconnected(_)) => self.capturing = false,
+        }
+    }
+
+    /// Block until the oldest copy is read back and return its buffer for reuse.
+    fn wait_for_oldest(&mut self, device: &wgpu::Device) -> Option<ReadbackBuffer> {
+        while self.free.is_empty() && !self.pending.is_empty() {
+            if let Err(e) = device.poll(wgpu::PollType::wait_indefinitely()) {
+                self.fail(GammaError::Render(format!(
+                    "Failed to wait for recorded frames: {}",
+                    e
+                )));
+                return None;
+            }
+            self.collect();
+        }
+        self.free.pop()
+    }
+
+    fn fail(&mut self, error: GammaError) {
+        self.capturing = false;
+        self.error.get_or_insert(error);
+    }
+
+    /// Wait for the encoder to write the file. Only call once no more frames are pending.
+    fn finish(mut self) -> Result<PathBuf, GammaError> {
+        // Closing the channel tells the encoder that no more frames are coming.
+        self.frames = None;
+
+        let result = match self.encoder.take().map(JoinHandle::join) {
+            Some(Ok(result)) => result,
+            Some(Err(_)) => Err(GammaError::Encode(
+                "The recording encoder panicked".to_string(),
+            )),
+            None => Err(GammaError::Encode(
+                "The recording encoder never started".to_string(),
+            )),
+        };
+
+        match self.error {
+            Some(error) => Err(error),
+            None => result,
+        }
+    }
+}
+
+impl<S> Gamma<S> {
+    /// Start recording the presented frames of the main window to a file.
+    ///
+    /// Frames are read back from the GPU and encoded on a background thread, so recording
+    /// does not slow the game down. Frames are skipped when the GPU or the encoder cannot keep
+    /// up, and the recording still plays back at the right speed. Recording stops after the
+    /// duration in the options, or with `stop_recording`, and the file is finished shortly after.
+    pub fn start_recording(&mut self, options: RecordingOptions) -> Result<(), GammaError> {
+        if self.recorder.is_some() {
+            return Err(GammaError::InvalidInput(
+                "A recording is already in progress".to_string(),
+            ));
+        }
+        if options.fps == Some(0) {
+            return Err(GammaError::InvalidInput(
+                "The recording frame rate must be above zero".to_string(),
+            ));
+        }
+        if self.device.is_none() {
+            return Err(GammaError::NotInitialized("Device"));
+        }
+
+        let frame_interval = options
+            .fps
+            .map(|fps| Duration::from_secs_f64(1.0 / fps as f64));
+        let sink = FrameSink::create(&options.path, options.format)?;
+        let (sender, receiver) = mpsc::sync_channel(ENCODER_QUEUE_SIZE);
+
+        let path = options.path.clone();
+        let last_delay = frame_interval.unwrap_or(Duration::from_secs_f64(1.0 / 60.0));
+        let encoder = std::thread::Builder::new()
+            .name("gamma-recording".to_string())
+            .spawn(move || encode_frames(sink, receiver, last_delay).map(|_| path))
+            .map_err(|e| GammaError::Encode(format!("Failed to start the encoder: {}", e)))?;
+
+        self.recording_result = None;
+        self.recorder = Some(Recorder {
+            capturing: true,
+            started: false,
+            wait_for_frames: self.headless_target.is_some(),
+            duration: options.duration,
+            frame_interval,
+            elapsed: Duration::ZERO,
+            next_capture: Duration::ZERO,
+            frames: Some(sender),
+            encoder: Some(encoder),
+            pending: VecDeque::new(),
+            free: Vec::new(),
+            error: None,
+        });
+
+        Ok(())
+    }
+
+    /// Stop recording early. The frames recorded so far are still saved.
+    pub fn stop_recording(&mut self) {
+        if let Some(recorder) = self.recorder.as_mut() {
+            recorder.capturing = false;
+        }
+    }
+
+    /// Whether frames are being recorded right now.
+    pub fn is_recording(&self) -> bool {
+        self.recorder
+            .as_ref()
+            .is_some_and(|recorder| recorder.capturing)
+    }
+
+    /// The outcome of the last recording once its file is completely written,
+    /// with the path it was saved to. Returns `None` while it is still being written.
+    pub fn take_recording_result(&mut self) -> Option<Result<PathBuf, GammaError>> {
+        self.recording_result.take()
+    }
+
+    /// Read back the finished frame for the recording and pass along the frames that arrived.
+    pub(crate) fn record_frame(&mut self) {
+        let (Some(recorder), Some(device), Some(queue)) = (
+            self.recorder.as_mut(),
+            self.device.as_ref(),
+            self.queue.as_ref(),
+        ) else {
+            return;
+        };
+
+        if recorder.capturing {
+            if recorder.started {
+                recorder.elapsed += self.delta;
+            }
+
+            if recorder.elapsed + CAPTURE_TOLERANCE >= recorder.duration {
+                recorder.capturing = false;
+            } else if recorder.elapsed + CAPTURE_TOLERANCE >= recorder.next_capture
+                && let Some(frame) = self.current_frame.as_ref()
+            {
+                recorder.started = true;
+                recorder.capture(device, queue, &frame.texture, recorder.elapsed);
+
+                if let Some(interval) = recorder.frame_interval {
+                    while recorder.next_capture <= recorder.elapsed + CAPTURE_TOLERANCE {
+                        recorder.next_capture += interval;
+                    }
+                }
+            }
+        }
+
+        let _ = device.poll(wgpu::PollType::Poll);
+        recorder.collect();
+
+        let encoded = recorder
+            .encoder
+            .as_ref()
+            .is_none_or(|encoder| encoder.is_finished());
+        if !recorder.capturing && recorder.pending.is_empty() {
+            // Dropping the sender lets the encoder finish, which is picked up on a later frame.
+            recorder.frames = None;
+            if encoded && let Some(recorder) = self.recorder.take() {
+                self.recording_result = Some(recorder.finish());
+            }
+        }
+    }
+
+    /// Stop recording and wait until the file is written, used when the game ends.
+    pub(crate) fn finish_recording(&mut self) {
+        let Some(mut recorder) = self.recorder.take() else {
+            return;
+        };
+        recorder.capturing = false;
+
+        if let Some(device) = self.device.as_ref() {
+            while !recorder.pending.is_empty() {
+                if let Err(e) = device.poll(wgpu::PollType::wait_indefinitely()) {
+                    recorder.fail(GammaError::Render(format!(
+                        "Failed to wait for recorded frames: {}",
+                        e
+                    )));
+                    break;
+                }
+                recorder.collect();
+            }
+        }
+
+        let result = recorder.finish();
+        if let Err(e) = &result {
+            eprintln!("Failed to save the recording: {}", e);
+        }
+        self.recording_result = Some(result);
+    }
+}
+
+/// The file a recording is written into, owned by the encoder thread.
+enum FrameSink {
+    Gif(Box<GifEncoder<BufWriter<File>>>),
+    Apng {
+        path: PathBuf,
+        file: Option<BufWriter<File>>,
+        writer: Option<png::Writer<BufWriter<File>>>,
+        frames: u32,
+    },
+    PngSequence {
+        directory: PathBuf,
+        frames: u32,
+    },
+}
+
+impl FrameSink {
+    /// Create the output file or directory up front, so problems are reported right away.
+    fn create(path: &Path, format: RecordingFormat) -> Result<Self, GammaError> {
+        match format {
+            RecordingFormat::Gif => {
+                let file = File::create(path).map_err(|e| GammaError::io(path, e))?;
+                let mut encoder = GifEncoder::new_with_speed(BufWriter::new(file), 10);
+                encoder
+                    .set_repeat(Repeat::Infinite)
+                    .map_err(|e| GammaError::Encode(format!("Failed to start the GIF: {}", e)))?;
+                Ok(FrameSink::Gif(Box::new(encoder)))
+            }
+            RecordingFormat::Apng => {
+                let file = File::create(path).map_err(|e| GammaError::io(path, e))?;
+                Ok(FrameSink::Apng {
+                    path: path.to_path_buf(),
+                    file: Some(BufWriter::new(file)),
+                    writer: None,
+                    frames: 0,
+                })
+            }
+            RecordingFormat::PngSequence => {
+                std::fs::create_dir_all(path).map_err(|e| GammaError::io(path, e))?;
+                Ok(FrameSink::PngSequence {
+                    directory: path.to_path_buf(),
+                    frames: 0,
+                })
+            }
+        }
+    }
+
+    fn write(&mut self, image: RgbaImage, delay: Duration) -> Result<(), GammaError> {
+        match self {
+            FrameSink::Gif(encoder) => {
+                let frame = image::Frame::from_parts(image, 0, 0, gif_delay(delay));
+                encoder
+                    .encode_frame(frame)
+                    .map_err(|e| GammaError::Encode(format!("Failed to encode a GIF frame: {}", e)))
+            }
+            FrameSink::Apng {
+                file,
+                writer,
+                frames,
+                ..
+            } => {
+                let writer = match writer {
+                    Some(writer) => writer,
+                    None => {
+                        let file = file.take().ok_or_else(|| {
+                            GammaError::Encode("The APNG file was already closed".to_string())
+                        })?;
+                        writer.insert(start_apng(file, image.width(), image.height())?)
+                    }
+                };
+
+                let delay_ms = (delay.as_secs_f64() * 1000.0).round().min(u16::MAX as f64) as u16;
+                writer
+                    .set_frame_delay(delay_ms, 1000)
+                    .and_then(|_| writer.write_image_data(image.as_raw()))
+                    .map_err(|e| {
+                        GammaError::Encode(format!("Failed to encode an APNG frame: {}", e))
+                    })?;
+                *frames += 1;
+                Ok(())
+            }
+            FrameSink::PngSequence { directory, frames } => {
+                let path = directory.join(format!("frame_{:05}.png", frames));
+                image
+                    .save_with_format(&path, ImageFormat::Png)
+                    .map_err(|e| match e {
+                        image::ImageError::IoError(source) => GammaError::io(&path, source),
+                        e => GammaError::Encode(format!(
+                            "Failed to save frame '{}': {}",
+                            path.display(),
+                            e
+                        )),
+                    })?;
+                *frames += 1;
+                Ok(())
+            }
+        }
+    }
+
+    fn finish(self) -> Result<(), GammaError> {
+        match self {
+            // The GIF trailer is written when the encoder is dropped.
+            FrameSink::Gif(_) => Ok(()),
+            FrameSink::Apng {
+                path,
+                writer,
+                frames,
+                ..
+            } => {
+                let Some(writer) = writer else {
+                    return Err(GammaError::Encode(
+                        "No frames were recorded for the APNG".to_string(),
+                    ));
+                };
+                writer
+                    .finish()
+                    .map_err(|e| GammaError::Encode(format!("Failed to finish the APNG: {}", e)))?;
+                set_apng_frame_count(&path, frames)
+            }
+            FrameSink::PngSequence { .. } => Ok(()),
+        }
+    }
+}
+
+/// GIF delays are whole hundredths of a second, so round instead of letting the encoder truncate.
+fn gif_delay(delay: Duration) -> Delay {
+    let centiseconds = (delay.as_secs_f64() * 100.0).round() as u64;
+    Delay::from_saturating_duration(Duration::from_millis(centiseconds * 10))
+}
+
+/// Write frames to the sink as they arrive, timing each one until the next.
+fn encode_frames(
+    mut sink: FrameSink,
+    frames: Receiver<RecordedFrame>,
+    last_delay: Duration,
+) -> Result<(), GammaError> {
+    let mut size = None;
+    let mut previous: Option<RecordedFrame> = None;
+    let mut last_delay = last_delay;
+
+    for mut frame in frames {
+        // Every frame keeps the size of the first, in case the window is resized while recording.
+        let (width, height) = *size.get_or_insert(frame.image.dimensions());
+        if frame.image.dimensions() != (width, height) {
+            frame.image = imageops::resize(&frame.image, width, height, FilterType::Triangle);
+        }
+
+        if let Some(previous) = previous.take() {
+            last_delay = frame.timestamp.saturating_sub(previous.timestamp);
+            sink.write(previous.image, last_delay)?;
+        }
+        previous = Some(frame);
+    }
+
+    if let Some(previous) = previous {
+        sink.write(previous.image, last_delay)?;
+    }
+
+    sink.finish()
+}
+
+fn start_apng(
+    file: BufWriter<File>,
+    width: u32,
+    height: u32,
+) -> Result<png::Writer<BufWriter<File>>, GammaError> {
+    let mut encoder = png::Encoder::new(file, width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    // The real count is only known at the end, so the placeholder must not be checked against.
+    encoder.validate_sequence(false);
+    encoder
+        .set_animated(APNG_PLACEHOLDER_FRAMES, 0)
+        .and_then(|_| encoder.write_header())
+        .map_err(|e| GammaError::Encode(format!("Failed to start the APNG: {}", e)))
+}
+
+/// Replace the placeholder frame count in the `acTL` chunk of a finished APNG file.
+fn set_apng_frame_count(path: &Path, frames: u32) -> Result<(), GammaError> {
+    let io_error = |e| GammaError::io(path, e);
+    let mut file = File::options()
+        .read(true)
+        .write(true)
+        .open(path)
+        .map_err(io_error)?;
+
+    // Skip the PNG signature, then walk the chunks: length, type, data and CRC.
+    let mut offset = 8;
+    loop {
+        let mut header = [0; 8];
+        file.seek(SeekFrom::Start(offset)).map_err(io_error)?;
+        file.read_exact(&mut header).map_err(io_error)?;
+
+        let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as u64;
+        let kind = &header[4..8];
+
+        if kind == b"acTL" {
+            let mut data = [0; 8];
+            file.read_exact(&mut data).map_err(io_error)?;
+            data[..4].copy_from_slice(&frames.to_be_bytes());
+
+            let mut crc = crc32fast::Hasher::new();
+            crc.update(kind);
+            crc.update(&data);
+
+            file.seek(SeekFrom::Start(offset + 8)).map_err(io_error)?;
+            file.write_all(&data).map_err(io_error)?;
+            file.write_all(&crc.finalize().to_be_bytes())
+                .map_err(io_error)?;
+            return Ok(());
+        }
+
+        if kind == b"IDAT" || kind == b"IEND" {
+            return Err(GammaError::Encode(
+                "The APNG has no animation control chunk".to_string(),
+            ));
+        }
+
+        offset += 12 + length;
+    }
+}
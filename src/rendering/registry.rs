@@ -0,0 +1,203 @@
+use std::{collections::HashMap, path::PathBuf, rc::Rc};
+
+use crate::{
+    error::GammaError,
+    gamma::Gamma,
+    rendering::{Flip, Texture},
+};
+
+/// A handle to a texture owned by the engine.
+///
+/// Handles stay cheap to copy and store in game state. Once the texture is unloaded
+/// the handle no longer resolves, even if its slot is reused by a later texture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TextureId {
+    index: u32,
+    generation: u32,
+}
+
+/// Where a texture came from, so loading the same image twice returns the same handle.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum TextureSource {
+    Path(PathBuf),
+    /// The encoded image itself, so different images can never share a handle.
+    Bytes(Rc<[u8]>),
+}
+
+struct Slot {
+    generation: u32,
+    entry: Option<Entry>,
+}
+
+struct Entry {
+    texture: Rc<Texture>,
+    source: Option<TextureSource>,
+    memory: u64,
+}
+
+/// The engine side store of textures referenced by `TextureId`.
+#[derive(Default)]
+pub(crate) struct TextureRegistry {
+    slots: Vec<Slot>,
+    free: Vec<u32>,
+    sources: HashMap<TextureSource, TextureId>,
+    memory: u64,
+}
+
+impl TextureRegistry {
+    fn insert(&mut self, texture: Texture, source: Option<TextureSource>) -> TextureId {
+        let memory = texture.memory_size();
+        let entry = Entry {
+            texture: Rc::new(texture),
+            source: source.clone(),
+            memory,
+        };
+
+        let id = match self.free.pop() {
+            Some(index) => {
+                let slot = &mut self.slots[index as usize];
+                slot.entry = Some(entry);
+                TextureId {
+                    index,
+                    generation: slot.generation,
+                }
+            }
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    entry: Some(entry),
+                });
+                TextureId {
+                    index: self.slots.len() as u32 - 1,
+                    generation: 0,
+                }
+            }
+        };
+
+        if let Some(source) = source {
+            self.sources.insert(source, id);
+        }
+        self.memory += memory;
+
+        id
+    }
+
+    fn get(&self, id: TextureId) -> Option<&Rc<Texture>> {
+        let slot = self.slots.get(id.index as usize)?;
+        if slot.generation != id.generation {
+            return None;
+        }
+        slot.entry.as_ref().map(|entry| &entry.texture)
+    }
+
+    fn remove(&mut self, id: TextureId) -> bool {
+        let Some(slot) = self.slots.get_mut(id.index as usize) else {
+            return false;
+        };
+        if slot.generation != id.generation {
+            return false;
+        }
+        let Some(entry) = slot.entry.take() else {
+            return false;
+        };
+
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free.push(id.index);
+        if let Some(source) = entry.source {
+            self.sources.remove(&source);
+        }
+        self.memory -= entry.memory;
+
+        true
+    }
+}
+
+impl Texture {
+    /// The GPU memory used by the texture and all of its mip levels in bytes.
+    pub(crate) fn memory_size(&self) -> u64 {
+        let bytes_per_pixel = self.texture.format().block_copy_size(None).unwrap_or(4) as u64;
+
+        (0..self.texture.mip_level_count())
+            .map(|level| {
+                let width = (self.width >> level).max(1) as u64;
+                let height = (self.height >> level).max(1) as u64;
+                width * height * bytes_per_pixel
+            })
+            .sum()
+    }
+}
+
+impl<S> Gamma<S> {
+    /// Load a texture from a file path into the engine and get a handle to it.
+    ///
+    /// Loading the same file again returns the existing handle instead of another copy.
+    pub fn load_texture_handle(&mut self, path: &str) -> Result<TextureId, GammaError> {
+        let source =
+            TextureSource::Path(std::fs::canonicalize(path).unwrap_or_else(|_| path.into()));
+        if let Some(&id) = self.textures.sources.get(&source) {
+            return Ok(id);
+        }
+
+        let texture = self.load_texture(path)?;
+        Ok(self.textures.insert(texture, Some(source)))
+    }
+
+    /// Load a texture from bytes into the engine and get a handle to it.
+    ///
+    /// Loading the same bytes again returns the existing handle instead of another copy. The
+    /// bytes are kept to compare against until the texture is unloaded.
+    pub fn load_texture_handle_from_bytes(
+        &mut self,
+        bytes: &[u8],
+    ) -> Result<TextureId, GammaError> {
+        let source = TextureSource::Bytes(bytes.into());
+        if let Some(&id) = self.textures.sources.get(&source) {
+            return Ok(id);
+        }
+
+        let texture = self.load_texture_from_bytes(bytes)?;
+        Ok(self.textures.insert(texture, Some(source)))
+    }
+
+    /// Hand a texture to the engine, such as one made with `create_texture`, and get a handle to it.
+    pub fn add_texture(&mut self, texture: Texture) -> TextureId {
+        self.textures.insert(texture, None)
+    }
+
+    /// Get the texture behind a handle, or `None` when it has been unloaded.
+    pub fn get_texture(&self, id: TextureId) -> Option<&Texture> {
+        self.textures.get(id).map(|texture| texture.as_ref())
+    }
+
+    /// Free the GPU memory of a texture. Returns false when the handle was already unloaded.
+    pub fn unload_texture(&mut self, id: TextureId) -> bool {
+        self.textures.remove(id)
+    }
+
+    /// The GPU memory in bytes used by all textures held by the engine.
+    pub fn texture_memory(&self) -> u64 {
+        self.textures.memory
+    }
+
+    /// Draw a texture held by the engine at a position. Unloaded handles draw nothing.
+    pub fn draw_texture_handle(&mut self, id: TextureId, x: f32, y: f32, flip: Flip) {
+        if let Some(texture) = self.textures.get(id).cloned() {
+            self.draw_texture(&texture, x, y, flip);
+        }
+    }
+
+    /// Draw a texture held by the engine stretched to a size. Unloaded handles draw nothing.
+    pub fn draw_texture_handle_scaled(
+        &mut self,
+        id: TextureId,
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        flip: Flip,
+    ) {
+        if let Some(texture) = self.textures.get(id).cloned() {
+            self.draw_texture_scaled(&texture, x, y, width, height, flip);
+        }
+    }
+}
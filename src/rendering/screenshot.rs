@@ -0,0 +1,52 @@
+use std::path::Path;
+
+use image::RgbaImage;
+
+use crate::{error::GammaError, gamma::Gamma, rendering::readback::ReadbackBuffer};
+
+impl<S> Gamma<S> {
+    /// Copy everything drawn so far this frame into an image.
+    ///
+    /// Call this at the end of your draw function to capture the whole frame.
+    pub fn capture_screenshot(&mut self) -> Result<RgbaImage, GammaError> {
+        let device = self
+            .device
+            .as_ref()
+            .ok_or(GammaError::NotInitialized("Device"))?;
+        let queue = self
+            .queue
+            .as_ref()
+            .ok_or(GammaError::NotInitialized("Queue"))?;
+        let frame = self
+            .current_frame
+            .as_ref()
+            .ok_or_else(|| GammaError::Render("Nothing has been drawn this frame".to_string()))?;
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Screenshot Encoder"),
+        });
+
+        let readback = ReadbackBuffer::copy_from_texture(device, &mut encoder, &frame.texture)?;
+
+        queue.submit(std::iter::once(encoder.finish()));
+        readback.map_blocking(device)?;
+        readback.to_image()
+    }
+
+    /// Capture the current frame and save it as a PNG file.
+    pub fn save_screenshot(&mut self, path: impl AsRef<Path>) -> Result<(), GammaError> {
+        let path = path.as_ref();
+        let image = self.capture_screenshot()?;
+
+        image
+            .save_with_format(path, image::ImageFormat::Png)
+            .map_err(|e| match e {
+                image::ImageError::IoError(source) => GammaError::io(path, source),
+                e => GammaError::Encode(format!(
+                    "Failed to save screenshot '{}': {}",
+                    path.display(),
+                    e
+                )),
+            })
+    }
+}
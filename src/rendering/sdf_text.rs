@@ -0,0 +1,921 @@
+use std::collections::HashMap;
+
+use glyph_brush::ab_glyph::{Font, FontArc, GlyphId, OutlineCurve, Point, PxScale, ScaleFont};
+use wgpu::{BindGroup, BindGroupLayout, Buffer, RenderPipeline, util::DeviceExt};
+
+use crate::{
+    error::GammaError,
+    gamma::Gamma,
+    rendering::{
+        Color,
+        stencil::{StencilMode, StencilPipelines},
+    },
+};
+
+/// The text size glyphs are turned into distance fields at. Any size is drawn from these.
+const SDF_BASE_SIZE: f32 = 64.0;
+/// How far from the glyph edge the distance field reaches, in pixels at the base size.
+const SDF_SPREAD: f32 = 16.0;
+const ATLAS_WIDTH: u32 = 1024;
+const INITIAL_ATLAS_HEIGHT: u32 = 256;
+/// Empty pixels between glyphs, so they do not bleed into each other when sampled.
+const GLYPH_PADDING: u32 = 1;
+/// The most line segments a curve of a glyph outline is split into.
+const MAX_CURVE_SEGMENTS: usize = 8;
+/// The length in pixels at the base size a curve needs for each line segment it is split into.
+const CURVE_SEGMENT_LENGTH: f32 = 3.0;
+
+const SDF_TEXT_SHADER: &str = r#"
+struct Params {
+    // The frame size and the offset of the viewport camera, both in world pixels.
+    screen: vec4<f32>,
+    fill: vec4<f32>,
+    outline: vec4<f32>,
+    glow: vec4<f32>,
+    shadow: vec4<f32>,
+    // Outline width, glow radius, shadow softness and the distance range of the atlas, all in
+    // screen pixels.
+    effects: vec4<f32>,
+}
+
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+    @location(1) uv: vec2<f32>,
+    @location(2) layer: f32,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) layer: f32,
+}
+
+@group(1) @binding(0)
+var<uniform> params: Params;
+
+@vertex
+fn vs_main(input: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(
+        (input.position.x + params.screen.z) / params.screen.x * 2.0 - 1.0,
+        1.0 - (input.position.y + params.screen.w) / params.screen.y * 2.0,
+        0.0,
+        1.0,
+    );
+    out.uv = input.uv;
+    out.layer = input.layer;
+    return out;
+}
+
+@group(0) @binding(0)
+var t_atlas: texture_2d<f32>;
+@group(0) @binding(1)
+var s_atlas: sampler;
+
+// Straight alpha `top` drawn over `bottom`.
+fn over(top: vec4<f32>, bottom: vec4<f32>) -> vec4<f32> {
+    let alpha = top.a + bottom.a * (1.0 - top.a);
+    let rgb = (top.rgb * top.a + bottom.rgb * bottom.a * (1.0 - top.a)) / max(alpha, 0.0001);
+    return vec4<f32>(rgb, alpha);
+}
+
+fn shade(in: VertexOutput) -> vec4<f32> {
+    // The distance to the glyph edge in screen pixels, positive inside the glyph.
+    let distance = (textureSample(t_atlas, s_atlas, in.uv).r - 0.5) * params.effects.w;
+    let smoothing = max(fwidth(distance) * 0.5, 0.0001);
+    let outer = distance + params.effects.x;
+
+    if (in.layer < 0.5) {
+        let softness = params.effects.z + smoothing;
+        let coverage = smoothstep(-softness, softness, outer);
+        return vec4<f32>(params.shadow.rgb, params.shadow.a * coverage);
+    }
+
+    let fill = smoothstep(-smoothing, smoothing, distance);
+    let outline = smoothstep(-smoothing, smoothing, outer);
+    var glow = 0.0;
+    if (params.effects.y > 0.0) {
+        glow = 1.0 - smoothstep(0.0, params.effects.y, -outer);
+    }
+
+    var color = vec4<f32>(params.glow.rgb, params.glow.a * glow);
+    color = over(vec4<f32>(params.outline.rgb, params.outline.a * outline), color);
+    return over(vec4<f32>(params.fill.rgb, params.fill.a * fill), color);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return shade(in);
+}
+
+@fragment
+fn fs_mask(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = shade(in);
+    if (color.a < 0.5) {
+        discard;
+    }
+    return color;
+}
+"#;
+
+/// How text drawn with `draw_sdf_text` looks.
+///
+/// Outlines, glows and shadow softness reach at most a quarter of the text size outside
+/// the glyphs and are shortened to fit.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextStyle {
+    /// The height of a line of text in pixels.
+    pub size: f32,
+    pub color: Color,
+    /// The outline width in pixels, or 0.0 for no outline.
+    pub outline_width: f32,
+    pub outline_color: Color,
+    /// How far the glow fades out around the text in pixels, or 0.0 for no glow.
+    pub glow_radius: f32,
+    pub glow_color: Color,
+    /// How far the shadow is moved from the text in pixels.
+    pub shadow_offset: [f32; 2],
+    /// How far the shadow edge is blurred in pixels.
+    pub shadow_softness: f32,
+    /// The shadow color. A transparent color draws no shadow.
+    pub shadow_color: Color,
+}
+
+impl Default for TextStyle {
+    fn default() -> Self {
+        Self {
+            size: 32.0,
+            color: Color::WHITE,
+            outline_width: 0.0,
+            outline_color: Color::BLACK,
+            glow_radius: 0.0,
+            glow_color: Color::WHITE,
+            shadow_offset: [2.0, 2.0],
+            shadow_softness: 0.0,
+            shadow_color: Color::TRANSPARENT,
+        }
+    }
+}
+
+impl TextStyle {
+    pub fn new(size: f32, color: impl Into<Color>) -> Self {
+        Self {
+            size,
+            color: color.into(),
+            ..Self::default()
+        }
+    }
+
+    pub fn with_outline(mut self, width: f32, color: impl Into<Color>) -> Self {
+        self.outline_width = width;
+        self.outline_color = color.into();
+        self
+    }
+
+    pub fn with_glow(mut self, radius: f32, color: impl Into<Color>) -> Self {
+        self.glow_radius = radius;
+        self.glow_color = color.into();
+        self
+    }
+
+    pub fn with_shadow(
+        mut self,
+        offset_x: f32,
+        offset_y: f32,
+        softness: f32,
+        color: impl Into<Color>,
+    ) -> Self {
+        self.shadow_offset = [offset_x, offset_y];
+        self.shadow_softness = softness;
+        self.shadow_color = color.into();
+        self
+    }
+}
+
+/// A font drawn from signed distance fields, so text stays crisp at any size and while it scales.
+///
+/// Each glyph is turned into a distance field once, the first time it is drawn, and kept in an
+/// atlas that grows as needed. Very sharp corners are slightly rounded when drawn much larger
+/// than 64 pixels.
+pub struct SdfFont {
+    font: FontArc,
+    /// `None` for glyphs without an outline, such as spaces.
+    glyphs: HashMap<GlyphId, Option<SdfGlyph>>,
+    /// One distance byte per pixel, `ATLAS_WIDTH` pixels wide.
+    atlas: Vec<u8>,
+    atlas_height: u32,
+    shelf: Shelf,
+    /// Whether the atlas changed since it was last uploaded.
+    dirty: bool,
+    gpu: Option<SdfAtlas>,
+}
+
+/// Where a glyph's distance field is in the atlas.
+#[derive(Debug, Clone, Copy)]
+struct SdfGlyph {
+    /// The top left of the field relative to the pen position on the baseline, at the base size.
+    offset: [f32; 2],
+    size: [u32; 2],
+    position: [u32; 2],
+}
+
+/// The row of the atlas glyphs are currently added to.
+struct Shelf {
+    x: u32,
+    y: u32,
+    height: u32,
+}
+
+struct SdfAtlas {
+    texture: wgpu::Texture,
+    bind_group: BindGroup,
+}
+
+impl SdfFont {
+    fn new(font: FontArc) -> Self {
+        Self {
+            font,
+            glyphs: HashMap::new(),
+            atlas: vec![0; (ATLAS_WIDTH * INITIAL_ATLAS_HEIGHT) as usize],
+            atlas_height: INITIAL_ATLAS_HEIGHT,
+            shelf: Shelf {
+                x: GLYPH_PADDING,
+                y: GLYPH_PADDING,
+                height: 0,
+            },
+            dirty: true,
+            gpu: None,
+        }
+    }
+
+    /// The width and height of text drawn at a size, in pixels.
+    pub fn measure(&self, text: &str, size: f32) -> (f32, f32) {
+        let layout = self.layout(text, size);
+        (layout.width, layout.height)
+    }
+
+    /// Position every glyph of the text relative to its top left corner.
+    fn layout(&self, text: &str, size: f32) -> TextLayout {
+        let font = self.font.as_scaled(PxScale::from(size));
+        let line_height = font.height() + font.line_gap();
+
+        let mut glyphs = Vec::new();
+        let mut pen = [0.0, font.ascent()];
+        let mut width: f32 = 0.0;
+        let mut lines = 1;
+        let mut previous = None;
+
+        for c in text.chars() {
+            if c == '\n' {
+                width = width.max(pen[0]);
+                pen = [0.0, pen[1] + line_height];
+                lines += 1;
+                previous = None;
+                continue;
+            }
+            if c.is_control() {
+                continue;
+            }
+
+            let id = font.glyph_id(c);
+            if let Some(previous) = previous {
+                pen[0] += font.kern(previous, id);
+            }
+            glyphs.push((id, pen));
+            pen[0] += font.h_advance(id);
+            previous = Some(id);
+        }
+
+        TextLayout {
+            glyphs,
+            width: width.max(pen[0]),
+            height: (lines - 1) as f32 * line_height + font.height(),
+        }
+    }
+
+    /// The distance field of a glyph, generating it the first time. The atlas is kept at most
+    /// `max_height` pixels tall.
+    fn glyph(&mut self, id: GlyphId, max_height: u32) -> Option<SdfGlyph> {
+        if let Some(glyph) = self.glyphs.get(&id) {
+            return *glyph;
+        }
+
+        let glyph = self.generate_glyph(id, max_height);
+        self.glyphs.insert(id, glyph);
+        glyph
+    }
+
+    fn generate_glyph(&mut self, id: GlyphId, max_height: u32) -> Option<SdfGlyph> {
+        let outline = self.font.outline(id)?;
+        let font = self.font.as_scaled(PxScale::from(SDF_BASE_SIZE));
+        let (scale_x, scale_y) = (font.h_scale_factor(), font.v_scale_factor());
+        // Outlines are in font units with y pointing up, the atlas has y pointing down.
+        let to_pixels = |point: Point| [point.x * scale_x, -point.y * scale_y];
+
+        let segments = flatten_outline(&outline.curves, to_pixels);
+        if segments.is_empty() {
+            return None;
+        }
+
+        let (mut min, mut max) = ([f32::MAX; 2], [f32::MIN; 2]);
+        for point in segments.iter().flat_map(|&(a, b)| [a, b]) {
+            min = [min[0].min(point[0]), min[1].min(point[1])];
+            max = [max[0].max(point[0]), max[1].max(point[1])];
+        }
+
+        let left = (min[0] - SDF_SPREAD).floor();
+        let top = (min[1] - SDF_SPREAD).floor();
+        let size = [
+            ((max[0] + SDF_SPREAD).ceil() - left) as u32,
+            ((max[1] + SDF_SPREAD).ceil() - top) as u32,
+        ];
+        let Some(position) = self.allocate(size, max_height) else {
+            eprintln!(
+                "Skipped glyph {}, the SDF atlas is full at {}x{}",
+                id.0, ATLAS_WIDTH, self.atlas_height
+            );
+            return None;
+        };
+
+        let field = distance_field(&segments, [left, top], size);
+        for row in 0..size[1] {
+            let start = ((position[1] + row) * ATLAS_WIDTH + position[0]) as usize;
+            let field_row = (row * size[0]) as usize;
+            for (pixel, &signed) in self.atlas[start..start + size[0] as usize]
+                .iter_mut()
+                .zip(&field[field_row..field_row + size[0] as usize])
+            {
+                let value = (0.5 + signed / (2.0 * SDF_SPREAD)).clamp(0.0, 1.0);
+                *pixel = (value * 255.0).round() as u8;
+            }
+        }
+
+        self.dirty = true;
+        Some(SdfGlyph {
+            offset: [left, top],
+            size,
+            position,
+        })
+    }
+
+    /// Find room for a glyph in the atlas, making the atlas taller when it is full.
+    /// Returns `None` when the glyph does not fit even at `max_height`.
+    fn allocate(&mut self, size: [u32; 2], max_height: u32) -> Option<[u32; 2]> {
+        if size[0] + 2 * GLYPH_PADDING > ATLAS_WIDTH {
+            return None;
+        }
+
+        // Start a new row when this one is full, without touching the shelf until the glyph fits.
+        let (x, y, height) = if self.shelf.x + size[0] + GLYPH_PADDING > ATLAS_WIDTH {
+            (
+                GLYPH_PADDING,
+                self.shelf.y + self.shelf.height + GLYPH_PADDING,
+                0,
+            )
+        } else {
+            (self.shelf.x, self.shelf.y, self.shelf.height)
+        };
+
+        let bottom = y + size[1] + GLYPH_PADDING;
+        if bottom > max_height {
+            return None;
+        }
+        if bottom > self.atlas_height {
+            self.atlas_height = bottom.next_power_of_two().min(max_height);
+            self.atlas
+                .resize((ATLAS_WIDTH * self.atlas_height) as usize, 0);
+        }
+
+        self.shelf = Shelf {
+            x: x + size[0] + GLYPH_PADDING,
+            y,
+            height: height.max(size[1]),
+        };
+        Some([x, y])
+    }
+
+    /// Upload the atlas if it changed, recreating the texture when it grew.
+    /// Returns the number of uploads.
+    fn upload(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layout: &BindGroupLayout,
+    ) -> u32 {
+        if !self.dirty {
+            return 0;
+        }
+
+        let size = wgpu::Extent3d {
+            width: ATLAS_WIDTH,
+            height: self.atlas_height,
+            depth_or_array_layers: 1,
+        };
+
+        if self
+            .gpu
+            .as_ref()
+            .is_none_or(|gpu| gpu.texture.height() != self.atlas_height)
+        {
+            let texture = device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("SDF Atlas Texture"),
+                size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::R8Unorm,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
+            });
+            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                ..Default::default()
+            });
+
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("SDF Atlas Bind Group"),
+                layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&sampler),
+                    },
+                ],
+            });
+
+            self.gpu = Some(SdfAtlas {
+                texture,
+                bind_group,
+            });
+        }
+
+        queue.write_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture: &self.gpu.as_ref().unwrap().texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            &self.atlas,
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(ATLAS_WIDTH),
+                rows_per_image: Some(self.atlas_height),
+            },
+            size,
+        );
+
+        self.dirty = false;
+        1
+    }
+}
+
+struct TextLayout {
+    /// Each glyph with its pen position on the baseline.
+    glyphs: Vec<(GlyphId, [f32; 2])>,
+    width: f32,
+    height: f32,
+}
+
+/// Turn the curves of an outline into straight segments in atlas pixels.
+fn flatten_outline(
+    curves: &[OutlineCurve],
+    to_pixels: impl Fn(Point) -> [f32; 2],
+) -> Vec<([f32; 2], [f32; 2])> {
+    let mut segments = Vec::new();
+
+    for curve in curves {
+        match *curve {
+            OutlineCurve::Line(a, b) => segments.push((to_pixels(a), to_pixels(b))),
+            OutlineCurve::Quad(a, b, c) => {
+                let (a, b, c) = (to_pixels(a), to_pixels(b), to_pixels(c));
+                let steps = curve_segments(&[a, b, c]);
+                let mut start = a;
+                for step in 1..=steps {
+                    let t = step as f32 / steps as f32;
+                    let u = 1.0 - t;
+                    let end = [
+                        u * u * a[0] + 2.0 * u * t * b[0] + t * t * c[0],
+                        u * u * a[1] + 2.0 * u * t * b[1] + t * t * c[1],
+                    ];
+                    segments.push((start, end));
+                    start = end;
+                }
+            }
+            OutlineCurve::Cubic(a, b, c, d) => {
+                let (a, b, c, d) = (to_pixels(a), to_pixels(b), to_pixels(c), to_pixels(d));
+                let steps = curve_segments(&[a, b, c, d]);
+                let mut start = a;
+                for step in 1..=steps {
+                    let t = step as f32 / steps as f32;
+                    let u = 1.0 - t;
+                    let (w0, w1, w2, w3) = (u * u * u, 3.0 * u * u * t, 3.0 * u * t * t, t * t * t);
+                    let end = [
+                        w0 * a[0] + w1 * b[0] + w2 * c[0] + w3 * d[0],
+                        w0 * a[1] + w1 * b[1] + w2 * c[1] + w3 * d[1],
+                    ];
+                    segments.push((start, end));
+                    start = end;
+                }
+            }
+        }
+    }
+
+    segments
+}
+
+/// How many line segments a curve is split into, judged by the length of its control points.
+fn curve_segments(points: &[[f32; 2]]) -> usize {
+    let length: f32 = points
+        .windows(2)
+        .map(|pair| ((pair[1][0] - pair[0][0]).powi(2) + (pair[1][1] - pair[0][1]).powi(2)).sqrt())
+        .sum();
+    ((length / CURVE_SEGMENT_LENGTH).ceil() as usize).clamp(1, MAX_CURVE_SEGMENTS)
+}
+
+fn segment_distance(point: [f32; 2], a: [f32; 2], b: [f32; 2]) -> f32 {
+    let (ab, ap) = (
+        [b[0] - a[0], b[1] - a[1]],
+        [point[0] - a[0], point[1] - a[1]],
+    );
+    let length_squared = ab[0] * ab[0] + ab[1] * ab[1];
+    let t = if length_squared > 0.0 {
+        ((ap[0] * ab[0] + ap[1] * ab[1]) / length_squared).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    let (dx, dy) = (ap[0] - ab[0] * t, ap[1] - ab[1] * t);
+    (dx * dx + dy * dy).sqrt()
+}
+
+/// The signed distance from the center of every pixel to the outline, positive inside.
+/// Distances are only exact up to `SDF_SPREAD`, which is as far as the atlas stores them.
+fn distance_field(segments: &[([f32; 2], [f32; 2])], origin: [f32; 2], size: [u32; 2]) -> Vec<f32> {
+    const CELL: u32 = 8;
+    let mut field = vec![SDF_SPREAD; (size[0] * size[1]) as usize];
+
+    // Only segments close to a block of pixels can be within reach of any of them.
+    for cell_y in (0..size[1]).step_by(CELL as usize) {
+        for cell_x in (0..size[0]).step_by(CELL as usize) {
+            let cell_min = [
+                origin[0] + cell_x as f32 - SDF_SPREAD,
+                origin[1] + cell_y as f32 - SDF_SPREAD,
+            ];
+            let cell_max = [
+                origin[0] + (cell_x + CELL) as f32 + SDF_SPREAD,
+                origin[1] + (cell_y + CELL) as f32 + SDF_SPREAD,
+            ];
+            let nearby: Vec<_> = segments
+                .iter()
+                .filter(|(a, b)| {
+                    a[0].max(b[0]) >= cell_min[0]
+                        && a[0].min(b[0]) <= cell_max[0]
+                        && a[1].max(b[1]) >= cell_min[1]
+                        && a[1].min(b[1]) <= cell_max[1]
+                })
+                .collect();
+
+            for row in cell_y..(cell_y + CELL).min(size[1]) {
+                for column in cell_x..(cell_x + CELL).min(size[0]) {
+                    let point = [
+                        origin[0] + column as f32 + 0.5,
+                        origin[1] + row as f32 + 0.5,
+                    ];
+                    let distance = nearby
+                        .iter()
+                        .map(|&&(a, b)| segment_distance(point, a, b))
+                        .fold(SDF_SPREAD, f32::min);
+                    field[(row * size[0] + column) as usize] = distance;
+                }
+            }
+        }
+    }
+
+    // A pixel is inside when the outline winds around it, counted by sweeping along each row.
+    for row in 0..size[1] {
+        let y = origin[1] + row as f32 + 0.5;
+        let mut crossings: Vec<(f32, i32)> = segments
+            .iter()
+            .filter(|(a, b)| (a[1] <= y) != (b[1] <= y))
+            .map(|&(a, b)| {
+                let t = (y - a[1]) / (b[1] - a[1]);
+                let direction = if b[1] > a[1] { 1 } else { -1 };
+                (a[0] + t * (b[0] - a[0]), direction)
+            })
+            .collect();
+        crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let mut winding = 0;
+        let mut next = 0;
+        for column in 0..size[0] {
+            let x = origin[0] + column as f32 + 0.5;
+            while next < crossings.len() && crossings[next].0 < x {
+                winding += crossings[next].1;
+                next += 1;
+            }
+            if winding == 0 {
+                let i = (row * size[0] + column) as usize;
+                field[i] = -field[i];
+            }
+        }
+    }
+
+    field
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct SdfTextVertex {
+    position: [f32; 2],
+    uv: [f32; 2],
+    /// 0.0 for the shadow and 1.0 for the text itself.
+    layer: f32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct SdfTextParams {
+    screen: [f32; 4],
+    fill: [f32; 4],
+    outline: [f32; 4],
+    glow: [f32; 4],
+    shadow: [f32; 4],
+    effects: [f32; 4],
+}
+
+/// Draws text from the distance field atlas of an `SdfFont`.
+pub struct SdfTextPipeline {
+    pub pipelines: StencilPipelines,
+    pub params_buffer: Buffer,
+    pub params_bind_group: BindGroup,
+}
+
+impl SdfTextPipeline {
+    pub fn new(
+        device: &wgpu::Device,
+        surface_format: wgpu::TextureFormat,
+        texture_bind_group_layout: &BindGroupLayout,
+        sample_count: u32,
+    ) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("SDF Text Shader"),
+            source: wgpu::ShaderSource::Wgsl(SDF_TEXT_SHADER.into()),
+        });
+
+        let params_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("SDF Text Params Bind Group Layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+            });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("SDF Text Pipeline Layout"),
+            bind_group_layouts: &[texture_bind_group_layout, &params_bind_group_layout],
+            immediate_size: 0,
+        });
+
+        let pipelines = StencilPipelines::new(
+            device,
+            &pipeline_layout,
+            &shader,
+            &[wgpu::VertexBufferLayout {
+                array_stride: std::mem::size_of::<SdfTextVertex>() as wgpu::BufferAddress,
+                step_mode: wgpu::VertexStepMode::Vertex,
+                attributes: &wgpu::vertex_attr_array![
+                    0 => Float32x2,
+                    1 => Float32x2,
+                    2 => Float32,
+                ],
+            }],
+            surface_format,
+            sample_count,
+            "SDF Text Render Pipeline",
+        );
+
+        let params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("SDF Text Params Buffer"),
+            size: std::mem::size_of::<SdfTextParams>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let params_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("SDF Text Params Bind Group"),
+            layout: &params_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: params_buffer.as_entire_binding(),
+            }],
+        });
+
+        Self {
+            pipelines,
+            params_buffer,
+            params_bind_group,
+        }
+    }
+
+    pub fn pipeline(&self, stencil: StencilMode) -> &RenderPipeline {
+        self.pipelines.get(stencil)
+    }
+}
+
+impl<S> Gamma<S> {
+    /// Load a TTF or OTF font for `draw_sdf_text`.
+    pub fn load_sdf_font(&self, path: &str) -> Result<SdfFont, GammaError> {
+        let bytes = std::fs::read(path).map_err(|e| GammaError::io(path, e))?;
+
+        self.load_sdf_font_from_bytes(&bytes)
+    }
+
+    /// Load a TTF or OTF font from bytes (works with include_bytes!).
+    pub fn load_sdf_font_from_bytes(&self, bytes: &[u8]) -> Result<SdfFont, GammaError> {
+        let font = FontArc::try_from_vec(bytes.to_vec())
+            .map_err(|e| GammaError::Decode(format!("Failed to load the font: {}", e)))?;
+
+        Ok(SdfFont::new(font))
+    }
+
+    /// Draw text with its top left corner at a position. Lines are split at `\n`.
+    ///
+    /// The text stays sharp at every size, so it can be scaled smoothly from frame to frame.
+    pub fn draw_sdf_text(
+        &mut self,
+        font: &mut SdfFont,
+        text: &str,
+        x: f32,
+        y: f32,
+        style: TextStyle,
+    ) {
+        if text.is_empty() || style.size <= 0.0 || !self.acquire_frame() {
+            return;
+        }
+
+        let (device, queue, texture_pipeline, frame) = match (
+            self.device.as_ref(),
+            self.queue.as_ref(),
+            self.texture_pipeline.as_ref(),
+            self.current_frame.as_ref(),
+        ) {
+            (Some(d), Some(q), Some(t), Some(f)) => (d, q, t, f),
+            _ => return,
+        };
+
+        let max_height = device.limits().max_texture_dimension_2d;
+        let layout = font.layout(text, style.size);
+        let glyphs: Vec<(SdfGlyph, [f32; 2])> = layout
+            .glyphs
+            .iter()
+            .filter_map(|&(id, pen)| font.glyph(id, max_height).map(|glyph| (glyph, pen)))
+            .collect();
+        if glyphs.is_empty() {
+            return;
+        }
+
+        let Some(scissor) = self.current_scissor(frame) else {
+            return;
+        };
+        let stencil = self.stencil_mode();
+        let view = self.view();
+
+        let sample_count = self.sample_count;
+        let pipeline = self.sdf_text_pipeline.get_or_insert_with(|| {
+            SdfTextPipeline::new(
+                device,
+                frame.texture.format(),
+                &texture_pipeline.bind_group_layout,
+                sample_count,
+            )
+        });
+
+        let mut buffer_uploads = font.upload(device, queue, &texture_pipeline.bind_group_layout);
+
+        // Effects cannot reach further than the distance field does.
+        let scale = style.size / SDF_BASE_SIZE;
+        let reach = SDF_SPREAD * scale;
+        let outline_width = style.outline_width.clamp(0.0, reach);
+        let glow_radius = style.glow_radius.clamp(0.0, reach - outline_width);
+        let shadow_softness = style.shadow_softness.clamp(0.0, reach - outline_width);
+
+        let params = SdfTextParams {
+            screen: view.screen(frame),
+            fill: style.color.to_linear_array(),
+            outline: style.outline_color.to_linear_array(),
+            glow: style.glow_color.to_linear_array(),
+            shadow: style.shadow_color.to_linear_array(),
+            effects: [outline_width, glow_radius, shadow_softness, 2.0 * reach],
+        };
+        queue.write_buffer(&pipeline.params_buffer, 0, bytemuck::bytes_of(&params));
+
+        let atlas_size = [ATLAS_WIDTH as f32, font.atlas_height as f32];
+        let mut layers = Vec::with_capacity(2);
+        if style.shadow_color.a > 0.0 {
+            layers.push((0.0, style.shadow_offset));
+        }
+        layers.push((1.0, [0.0, 0.0]));
+
+        let mut vertices = Vec::with_capacity(glyphs.len() * layers.len() * 4);
+        for &(layer, offset) in &layers {
+            for (glyph, pen) in &glyphs {
+                let left = x + offset[0] + pen[0] + glyph.offset[0] * scale;
+                let top = y + offset[1] + pen[1] + glyph.offset[1] * scale;
+                let right = left + glyph.size[0] as f32 * scale;
+                let bottom = top + glyph.size[1] as f32 * scale;
+
+                let uv_left = glyph.position[0] as f32 / atlas_size[0];
+                let uv_top = glyph.position[1] as f32 / atlas_size[1];
+                let uv_right = (glyph.position[0] + glyph.size[0]) as f32 / atlas_size[0];
+                let uv_bottom = (glyph.position[1] + glyph.size[1]) as f32 / atlas_size[1];
+
+                vertices.extend([
+                    SdfTextVertex {
+                        position: [left, top],
+                        uv: [uv_left, uv_top],
+                        layer,
+                    },
+                    SdfTextVertex {
+                        position: [right, top],
+                        uv: [uv_right, uv_top],
+                        layer,
+                    },
+                    SdfTextVertex {
+                        position: [left, bottom],
+                        uv: [uv_left, uv_bottom],
+                        layer,
+                    },
+                    SdfTextVertex {
+                        position: [right, bottom],
+                        uv: [uv_right, uv_bottom],
+                        layer,
+                    },
+                ]);
+            }
+        }
+
+        let indices: Vec<u32> = (0..(vertices.len() / 4) as u32)
+            .flat_map(|quad| {
+                let base = quad * 4;
+                [base, base + 1, base + 2, base + 1, base + 3, base + 2]
+            })
+            .collect();
+
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("SDF Text Vertex Buffer"),
+            contents: bytemuck::cast_slice(&vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("SDF Text Index Buffer"),
+            contents: bytemuck::cast_slice(&indices),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+        buffer_uploads += 3;
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("SDF Text Render Encoder"),
+        });
+
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("SDF Text Render Pass"),
+                color_attachments: &[Some(frame.color_attachment(wgpu::LoadOp::Load))],
+                depth_stencil_attachment: frame.depth_stencil_attachment(),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+                multiview_mask: None,
+            });
+
+            scissor.apply(&mut render_pass);
+            stencil.apply(&mut render_pass);
+            render_pass.set_pipeline(pipeline.pipeline(stencil));
+            render_pass.set_bind_group(0, &font.gpu.as_ref().unwrap().bind_group, &[]);
+            render_pass.set_bind_group(1, &pipeline.params_bind_group, &[]);
+            render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+            render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+            render_pass.draw_indexed(0..indices.len() as u32, 0, 0..1);
+        }
+
+        queue.submit(std::iter::once(encoder.finish()));
+
+        self.frame_stats.batches += 1;
+        self.frame_stats.draw_calls += 1;
+        self.frame_stats.vertices += vertices.len() as u32;
+        self.frame_stats.texture_binds += 1;
+        self.frame_stats.buffer_uploads += buffer_uploads;
+    }
+}
@@ -0,0 +1,514 @@
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+use wgpu::{BindGroup, BindGroupLayout, Buffer, RenderPipeline, ShaderModule, util::DeviceExt};
+
+use crate::{
+    error::GammaError,
+    gamma::Gamma,
+    rendering::{
+        Flip, Texture,
+        stencil::{StencilMode, StencilPipelines},
+        text::TextRenderer,
+        texture::sprite_vertices,
+    },
+};
+
+/// How often a shader file is checked for changes.
+const RELOAD_CHECK_INTERVAL: Duration = Duration::from_millis(250);
+const ERROR_TEXT_SIZE: f32 = 16.0;
+const ERROR_MARGIN: f32 = 8.0;
+
+/// Added after the code of every shader loaded with `load_shader`. It comes after the file's
+/// own code so line numbers in compile errors match the file.
+const SHADER_PRELUDE: &str = r#"
+struct Globals {
+    screen_size: vec2<f32>,
+    time: f32,
+    delta_time: f32,
+}
+
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+    @location(1) tex_coords: vec2<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+}
+
+@group(0) @binding(0)
+var t_diffuse: texture_2d<f32>;
+@group(0) @binding(1)
+var s_diffuse: sampler;
+@group(1) @binding(0)
+var<uniform> globals: Globals;
+
+@vertex
+fn vs_main(input: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(input.position, 0.0, 1.0);
+    out.tex_coords = input.tex_coords;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return fragment(in);
+}
+
+@fragment
+fn fs_mask(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = fragment(in);
+    if (color.a < 0.5) {
+        discard;
+    }
+    return color;
+}
+"#;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct Globals {
+    screen_size: [f32; 2],
+    time: f32,
+    delta_time: f32,
+}
+
+/// A WGSL file that shades sprites drawn with `draw_texture_with_shader`, reloaded whenever
+/// the file changes.
+///
+/// The file defines `fn fragment(in: VertexOutput) -> vec4<f32>`, which returns the color of
+/// one pixel of the sprite. It can use:
+///
+/// - `in.tex_coords`, the texture coordinates from 0.0 to 1.0
+/// - `t_diffuse` and `s_diffuse`, the sprite texture and its sampler
+/// - `globals.screen_size` in pixels, and `globals.time` and `globals.delta_time` in seconds
+///
+/// When a changed file fails to compile or does not fit these bindings, the last version that
+/// worked keeps being used and the error is shown on screen until the file is fixed.
+pub struct Shader {
+    path: PathBuf,
+    /// The last version of the file that compiled and built pipelines.
+    module: ShaderModule,
+    /// Built from `module` on first use.
+    pipelines: Option<ShaderPipelines>,
+    /// Whether `module` failed to build pipelines, so it is not tried again every frame.
+    module_failed: bool,
+    globals_bind_group_layout: BindGroupLayout,
+    globals_buffer: Buffer,
+    globals_bind_group: BindGroup,
+    modified: Option<SystemTime>,
+    last_check: Instant,
+    error: Option<String>,
+}
+
+struct ShaderPipelines {
+    format: wgpu::TextureFormat,
+    sample_count: u32,
+    pipelines: StencilPipelines,
+}
+
+impl Shader {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The error from the last time the file changed and failed to compile or build pipelines,
+    /// if it still does.
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    /// Compile the file again if it changed since the last check. The new version is only used
+    /// once it also builds pipelines for the frame, until then the last working one is kept.
+    fn reload_if_changed(
+        &mut self,
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        texture_bind_group_layout: &BindGroupLayout,
+        sample_count: u32,
+    ) {
+        if self.last_check.elapsed() < RELOAD_CHECK_INTERVAL {
+            return;
+        }
+        self.last_check = Instant::now();
+
+        let modified = modified_time(&self.path);
+        if modified == self.modified {
+            return;
+        }
+        self.modified = modified;
+
+        let result = std::fs::read_to_string(&self.path)
+            .map_err(|e| GammaError::io(&self.path, e))
+            .and_then(|source| compile_shader(device, &self.path, &source))
+            .and_then(|module| {
+                let pipelines = self.create_pipelines(
+                    device,
+                    &module,
+                    format,
+                    texture_bind_group_layout,
+                    sample_count,
+                )?;
+                Ok((module, pipelines))
+            });
+
+        match result {
+            Ok((module, pipelines)) => {
+                self.module = module;
+                self.module_failed = false;
+                self.pipelines = Some(pipelines);
+                self.error = None;
+            }
+            Err(e) => self.set_error(e),
+        }
+    }
+
+    /// Build the pipelines from `module` if there are none for the frame's format yet. A module
+    /// that failed to build them is not tried again until the file changes.
+    fn update_pipelines(
+        &mut self,
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        texture_bind_group_layout: &BindGroupLayout,
+        sample_count: u32,
+    ) {
+        let matches = self
+            .pipelines
+            .as_ref()
+            .is_some_and(|p| p.format == format && p.sample_count == sample_count);
+        if matches || self.module_failed {
+            return;
+        }
+
+        let result = self.create_pipelines(
+            device,
+            &self.module,
+            format,
+            texture_bind_group_layout,
+            sample_count,
+        );
+        match result {
+            Ok(pipelines) => self.pipelines = Some(pipelines),
+            Err(e) => {
+                self.pipelines = None;
+                self.module_failed = true;
+                self.set_error(e);
+            }
+        }
+    }
+
+    /// Build the pipelines for a module, catching the errors of code that compiles but does not
+    /// fit the bindings the engine provides.
+    fn create_pipelines(
+        &self,
+        device: &wgpu::Device,
+        module: &ShaderModule,
+        format: wgpu::TextureFormat,
+        texture_bind_group_layout: &BindGroupLayout,
+        sample_count: u32,
+    ) -> Result<ShaderPipelines, GammaError> {
+        let scope = device.push_error_scope(wgpu::ErrorFilter::Validation);
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Custom Shader Pipeline Layout"),
+            bind_group_layouts: &[texture_bind_group_layout, &self.globals_bind_group_layout],
+            immediate_size: 0,
+        });
+
+        let pipelines = StencilPipelines::new(
+            device,
+            &pipeline_layout,
+            module,
+            &[wgpu::VertexBufferLayout {
+                array_stride: 4 * 4,
+                step_mode: wgpu::VertexStepMode::Vertex,
+                attributes: &wgpu::vertex_attr_array![
+                    0 => Float32x2,
+                    1 => Float32x2,
+                ],
+            }],
+            format,
+            sample_count,
+            "Custom Shader Render Pipeline",
+        );
+
+        match pollster::block_on(scope.pop()) {
+            Some(error) => Err(GammaError::Shader(error.to_string())),
+            None => Ok(ShaderPipelines {
+                format,
+                sample_count,
+                pipelines,
+            }),
+        }
+    }
+
+    fn set_error(&mut self, error: GammaError) {
+        let message = error.to_string();
+        if self.error.as_ref() != Some(&message) {
+            eprintln!("Failed to reload '{}': {}", self.path.display(), message);
+        }
+        self.error = Some(message);
+    }
+
+    /// The pipeline to draw with, `None` when the shader has no working pipelines for the frame.
+    fn pipeline(&self, stencil: StencilMode) -> Option<&RenderPipeline> {
+        self.pipelines.as_ref().map(|p| p.pipelines.get(stencil))
+    }
+}
+
+/// Compile WGSL from a shader file, catching errors instead of letting wgpu panic on them.
+fn compile_shader(
+    device: &wgpu::Device,
+    path: &Path,
+    source: &str,
+) -> Result<ShaderModule, GammaError> {
+    let scope = device.push_error_scope(wgpu::ErrorFilter::Validation);
+    let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some(&path.display().to_string()),
+        source: wgpu::ShaderSource::Wgsl(format!("{}\n{}", source, SHADER_PRELUDE).into()),
+    });
+
+    let Some(error) = pollster::block_on(scope.pop()) else {
+        return Ok(module);
+    };
+
+    // The compilation messages point at the line in the file, which is easier to act on than
+    // the whole validation error.
+    let info = pollster::block_on(module.get_compilation_info());
+    let messages: Vec<&str> = info
+        .messages
+        .iter()
+        .filter(|m| m.message_type == wgpu::CompilationMessageType::Error)
+        .map(|m| m.message.trim())
+        .collect();
+
+    if messages.is_empty() {
+        Err(GammaError::Shader(error.to_string()))
+    } else {
+        Err(GammaError::Shader(messages.join("\n")))
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+impl<S> Gamma<S> {
+    /// Load a WGSL shader for `draw_texture_with_shader`. See `Shader` for what the file contains.
+    pub fn load_shader(&self, path: &str) -> Result<Shader, GammaError> {
+        let device = self
+            .device
+            .as_ref()
+            .ok_or(GammaError::NotInitialized("Device"))?;
+
+        let modified = modified_time(Path::new(path));
+        let source = std::fs::read_to_string(path).map_err(|e| GammaError::io(path, e))?;
+        let module = compile_shader(device, Path::new(path), &source)?;
+
+        let globals_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Custom Shader Globals Bind Group Layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+            });
+
+        let globals_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Custom Shader Globals Buffer"),
+            size: std::mem::size_of::<Globals>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let globals_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Custom Shader Globals Bind Group"),
+            layout: &globals_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: globals_buffer.as_entire_binding(),
+            }],
+        });
+
+        Ok(Shader {
+            path: PathBuf::from(path),
+            module,
+            pipelines: None,
+            module_failed: false,
+            globals_bind_group_layout,
+            globals_buffer,
+            globals_bind_group,
+            modified,
+            last_check: Instant::now(),
+            error: None,
+        })
+    }
+
+    /// Draw a texture shaded by a custom shader, reloading the shader first if its file changed.
+    pub fn draw_texture_with_shader(
+        &mut self,
+        texture: &Texture,
+        shader: &mut Shader,
+        x: f32,
+        y: f32,
+        flip: Flip,
+    ) {
+        if !self.acquire_frame() {
+            return;
+        }
+
+        let (device, queue, texture_pipeline, frame) = match (
+            self.device.as_ref(),
+            self.queue.as_ref(),
+            self.texture_pipeline.as_ref(),
+            self.current_frame.as_ref(),
+        ) {
+            (Some(d), Some(q), Some(t), Some(f)) => (d, q, t, f),
+            _ => return,
+        };
+
+        let format = frame.texture.format();
+        let layout = &texture_pipeline.bind_group_layout;
+        shader.reload_if_changed(device, format, layout, self.sample_count);
+        shader.update_pipelines(device, format, layout, self.sample_count);
+        if let Some(error) = &shader.error {
+            let message = format!("Failed to reload '{}':\n{}", shader.path.display(), error);
+            if !self.shader_errors.contains(&message) {
+                self.shader_errors.push(message);
+            }
+        }
+
+        let Some(scissor) = self.current_scissor(frame) else {
+            return;
+        };
+        let stencil = self.stencil_mode();
+        let Some(pipeline) = shader.pipeline(stencil) else {
+            return;
+        };
+
+        let globals = Globals {
+            screen_size: [frame.width(), frame.height()],
+            time: self.elapsed.as_secs_f32(),
+            delta_time: self.delta.as_secs_f32(),
+        };
+        queue.write_buffer(&shader.globals_buffer, 0, bytemuck::bytes_of(&globals));
+
+        let view = self.view();
+        let (x, y) = view.point(x, y);
+        let vertices = sprite_vertices(
+            frame.width(),
+            frame.height(),
+            x,
+            y,
+            view.length(texture.width as f32),
+            view.length(texture.height as f32),
+            flip,
+        );
+        let indices: [u16; 6] = [0, 1, 2, 1, 3, 2];
+
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Custom Shader Vertex Buffer"),
+            contents: bytemuck::cast_slice(&vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Custom Shader Index Buffer"),
+            contents: bytemuck::cast_slice(&indices),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Custom Shader Texture Bind Group"),
+            layout: &texture_pipeline.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&texture.sampler),
+                },
+            ],
+        });
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Custom Shader Render Encoder"),
+        });
+
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Custom Shader Render Pass"),
+                color_attachments: &[Some(frame.color_attachment(wgpu::LoadOp::Load))],
+                depth_stencil_attachment: frame.depth_stencil_attachment(),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+                multiview_mask: None,
+            });
+
+            scissor.apply(&mut render_pass);
+            stencil.apply(&mut render_pass);
+            render_pass.set_pipeline(pipeline);
+            render_pass.set_bind_group(0, &bind_group, &[]);
+            render_pass.set_bind_group(1, &shader.globals_bind_group, &[]);
+            render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+            render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+            render_pass.draw_indexed(0..6, 0, 0..1);
+        }
+
+        queue.submit(std::iter::once(encoder.finish()));
+
+        self.frame_stats.batches += 1;
+        self.frame_stats.draw_calls += 1;
+        self.frame_stats.vertices += vertices.len() as u32;
+        self.frame_stats.texture_binds += 1;
+        self.frame_stats.buffer_uploads += 3;
+
+        self.draw_sprite_normals(texture, None, &vertices, flip, scissor);
+    }
+
+    /// Show the errors of shaders that failed to reload this frame in the bottom left corner.
+    pub(crate) fn draw_shader_errors(&mut self) {
+        let errors = std::mem::take(&mut self.shader_errors);
+        if errors.is_empty() || !self.acquire_frame() {
+            return;
+        }
+
+        let sample_count = self.sample_count;
+
+        let (device, queue, frame) = match (
+            self.device.as_ref(),
+            self.queue.as_ref(),
+            self.current_frame.as_ref(),
+        ) {
+            (Some(d), Some(q), Some(f)) => (d, q, f),
+            _ => return,
+        };
+
+        let text_renderer = self
+            .text_renderer
+            .get_or_insert_with(|| TextRenderer::new(device, frame.texture.format(), sample_count));
+
+        let lines: Vec<&str> = errors.iter().flat_map(|error| error.lines()).collect();
+        let top = frame.height() - ERROR_MARGIN - lines.len() as f32 * ERROR_TEXT_SIZE;
+
+        for (i, line) in lines.iter().enumerate() {
+            let (x, y) = (ERROR_MARGIN, top + i as f32 * ERROR_TEXT_SIZE);
+            text_renderer.queue_with_shadow(line, x, y, ERROR_TEXT_SIZE, [1.0, 0.2, 0.2, 1.0]);
+        }
+
+        text_renderer.draw(device, queue, frame, &mut self.frame_stats);
+    }
+}
@@ -0,0 +1,560 @@
+use wgpu::{BindGroupLayout, Buffer, RenderPipeline, TextureView, util::DeviceExt};
+
+use crate::{
+    error::GammaError,
+    gamma::Gamma,
+    rendering::{
+        Color, Flip, Texture,
+        pipeline::texture_shader,
+        stencil::{StencilMode, StencilPipelines},
+        texture::sprite_vertices,
+    },
+};
+
+/// Outlines are capped at this many texture pixels, since every pixel of one looks this far around it.
+const MAX_OUTLINE_WIDTH: f32 = 16.0;
+
+/// An effect applied to a sprite while it is drawn with `draw_texture_with_effect`.
+#[derive(Clone, Copy)]
+pub enum SpriteEffect<'a> {
+    /// Blend the sprite towards a solid color, with `amount` from 0.0 for none to 1.0 for fully.
+    Flash { color: Color, amount: f32 },
+    /// Surround the sprite with an outline `width` texture pixels wide.
+    Outline { width: f32, color: Color },
+    /// Eat the sprite away in a noise pattern, with `amount` from 0.0 for none to 1.0 for all of
+    /// it. The pixels next to the eaten ones, up to `edge_width` further along, take `edge_color`.
+    Dissolve {
+        amount: f32,
+        edge_width: f32,
+        edge_color: Color,
+    },
+    /// Color an indexed sprite from a palette, such as one made with `create_palette`.
+    ///
+    /// The red channel of every pixel of the sprite is the index of its color within the
+    /// palette's `row`, and its alpha is kept.
+    PaletteSwap { palette: &'a Texture, row: u32 },
+}
+
+impl<'a> SpriteEffect<'a> {
+    pub fn flash(color: impl Into<Color>, amount: f32) -> Self {
+        Self::Flash {
+            color: color.into(),
+            amount,
+        }
+    }
+
+    pub fn outline(width: f32, color: impl Into<Color>) -> Self {
+        Self::Outline {
+            width,
+            color: color.into(),
+        }
+    }
+
+    pub fn dissolve(amount: f32, edge_width: f32, edge_color: impl Into<Color>) -> Self {
+        Self::Dissolve {
+            amount,
+            edge_width,
+            edge_color: edge_color.into(),
+        }
+    }
+
+    pub fn palette_swap(palette: &'a Texture, row: u32) -> Self {
+        Self::PaletteSwap { palette, row }
+    }
+
+    /// The index of this effect's pipelines in `SpriteEffectPipeline`, in the order of `EFFECT_SHADERS`.
+    fn index(&self) -> usize {
+        match self {
+            Self::Flash { .. } => 0,
+            Self::Outline { .. } => 1,
+            Self::Dissolve { .. } => 2,
+            Self::PaletteSwap { .. } => 3,
+        }
+    }
+
+    fn params(&self, texture: &Texture) -> EffectParams {
+        let (color, amount, width, palette_row) = match *self {
+            Self::Flash { color, amount } => (color, amount, 0.0, 0),
+            Self::Outline { width, color } => (color, 0.0, outline_width(width), 0),
+            Self::Dissolve {
+                amount,
+                edge_width,
+                edge_color,
+            } => (edge_color, amount, edge_width, 0),
+            Self::PaletteSwap { row, .. } => (Color::TRANSPARENT, 0.0, 0.0, row),
+        };
+
+        EffectParams {
+            color: color.to_linear_array(),
+            texture_size: [texture.width as f32, texture.height as f32],
+            amount,
+            width,
+            palette_row,
+            _padding: [0; 3],
+        }
+    }
+}
+
+fn outline_width(width: f32) -> f32 {
+    width.clamp(0.0, MAX_OUTLINE_WIDTH)
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct EffectParams {
+    color: [f32; 4],
+    texture_size: [f32; 2],
+    amount: f32,
+    width: f32,
+    palette_row: u32,
+    _padding: [u32; 3],
+}
+
+/// The parameters every effect shader reads, added before its `sprite_color`.
+const EFFECT_PARAMS_SHADER: &str = r#"
+struct EffectParams {
+    color: vec4<f32>,
+    texture_size: vec2<f32>,
+    amount: f32,
+    width: f32,
+    palette_row: u32,
+    padding0: u32,
+    padding1: u32,
+    padding2: u32,
+}
+
+@group(1) @binding(0)
+var<uniform> params: EffectParams;
+@group(1) @binding(1)
+var t_palette: texture_2d<f32>;
+"#;
+
+const FLASH_SHADER: &str = r#"
+fn sprite_color(in: VertexOutput) -> vec4<f32> {
+    let color = textureSample(t_diffuse, s_diffuse, in.tex_coords);
+    return vec4<f32>(mix(color.rgb, params.color.rgb, params.amount * params.color.a), color.a);
+}
+"#;
+
+const OUTLINE_SHADER: &str = r#"
+// The quad is grown to make room for the outline, so everything outside the texture is transparent.
+fn sample_inside(uv: vec2<f32>) -> vec4<f32> {
+    let inside = all(uv >= vec2<f32>(0.0)) && all(uv <= vec2<f32>(1.0));
+    return select(vec4<f32>(0.0), textureSampleLevel(t_diffuse, s_diffuse, uv, 0.0), inside);
+}
+
+fn sprite_color(in: VertexOutput) -> vec4<f32> {
+    let color = sample_inside(in.tex_coords);
+
+    let radius = i32(ceil(params.width));
+    var coverage = 0.0;
+    for (var y = -radius; y <= radius; y++) {
+        for (var x = -radius; x <= radius; x++) {
+            let offset = vec2<f32>(f32(x), f32(y));
+            if (dot(offset, offset) <= params.width * params.width) {
+                let neighbor = sample_inside(in.tex_coords + offset / params.texture_size);
+                coverage = max(coverage, neighbor.a);
+            }
+        }
+    }
+
+    // The sprite goes over its outline.
+    let outline_alpha = params.color.a * coverage * (1.0 - color.a);
+    let alpha = color.a + outline_alpha;
+    if (alpha <= 0.0) {
+        return vec4<f32>(0.0);
+    }
+    return vec4<f32>((color.rgb * color.a + params.color.rgb * outline_alpha) / alpha, alpha);
+}
+"#;
+
+const DISSOLVE_SHADER: &str = r#"
+fn hash(p: vec2<f32>) -> f32 {
+    return fract(sin(dot(p, vec2<f32>(127.1, 311.7))) * 43758.5453);
+}
+
+fn value_noise(p: vec2<f32>) -> f32 {
+    let cell = floor(p);
+    let f = fract(p);
+    let u = f * f * (3.0 - 2.0 * f);
+
+    let a = hash(cell);
+    let b = hash(cell + vec2<f32>(1.0, 0.0));
+    let c = hash(cell + vec2<f32>(0.0, 1.0));
+    let d = hash(cell + vec2<f32>(1.0, 1.0));
+    return mix(mix(a, b, u.x), mix(c, d, u.x), u.y);
+}
+
+fn sprite_color(in: VertexOutput) -> vec4<f32> {
+    let color = textureSample(t_diffuse, s_diffuse, in.tex_coords);
+
+    // Noise in texture pixels, so the pattern stays the same however the sprite is scaled.
+    let texel = in.tex_coords * params.texture_size;
+    let noise = value_noise(texel / 8.0) * 0.7 + value_noise(texel / 2.0) * 0.3;
+
+    if (noise < params.amount) {
+        return vec4<f32>(0.0);
+    }
+    if (params.amount > 0.0 && noise < params.amount + params.width) {
+        return vec4<f32>(mix(color.rgb, params.color.rgb, params.color.a), color.a);
+    }
+    return color;
+}
+"#;
+
+const PALETTE_SWAP_SHADER: &str = r#"
+fn linear_to_srgb(value: f32) -> f32 {
+    if (value <= 0.0031308) {
+        return value * 12.92;
+    }
+    return 1.055 * pow(value, 1.0 / 2.4) - 0.055;
+}
+
+fn sprite_color(in: VertexOutput) -> vec4<f32> {
+    // Indices are read without filtering, blending two of them would give an unrelated color.
+    let size = vec2<i32>(textureDimensions(t_diffuse));
+    let texel = clamp(vec2<i32>(in.tex_coords * vec2<f32>(size)), vec2<i32>(0), size - 1);
+    let color = textureLoad(t_diffuse, texel, 0);
+
+    // Textures are stored as sRGB, so the red channel is converted back to the index as saved.
+    let index = u32(round(linear_to_srgb(color.r) * 255.0));
+    let palette_size = textureDimensions(t_palette);
+    let entry = textureLoad(
+        t_palette,
+        vec2<u32>(min(index, palette_size.x - 1u), min(params.palette_row, palette_size.y - 1u)),
+        0,
+    );
+    return vec4<f32>(entry.rgb, entry.a * color.a);
+}
+"#;
+
+/// The `sprite_color` of every effect, in the order of `SpriteEffect::index`.
+const EFFECT_SHADERS: [(&str, &str); 4] = [
+    ("Flash", FLASH_SHADER),
+    ("Outline", OUTLINE_SHADER),
+    ("Dissolve", DISSOLVE_SHADER),
+    ("Palette Swap", PALETTE_SWAP_SHADER),
+];
+
+/// Draws sprites with a `SpriteEffect`, using variants of the texture shader.
+pub struct SpriteEffectPipeline {
+    pub params_bind_group_layout: BindGroupLayout,
+    /// The pipelines of every effect, in the order of `EFFECT_SHADERS`.
+    pub pipelines: Vec<StencilPipelines>,
+    pub params_buffer: Buffer,
+    /// Bound in place of a palette by effects that do not use one.
+    pub empty_palette: TextureView,
+}
+
+impl SpriteEffectPipeline {
+    pub fn new(
+        device: &wgpu::Device,
+        surface_format: wgpu::TextureFormat,
+        texture_bind_group_layout: &BindGroupLayout,
+        sample_count: u32,
+    ) -> Self {
+        let params_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Sprite Effect Bind Group Layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                ],
+            });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Sprite Effect Pipeline Layout"),
+            bind_group_layouts: &[texture_bind_group_layout, &params_bind_group_layout],
+            immediate_size: 0,
+        });
+
+        let mut pipelines = Vec::new();
+        for (name, effect_shader) in EFFECT_SHADERS {
+            let source = texture_shader(&format!("{}\n{}", EFFECT_PARAMS_SHADER, effect_shader));
+            let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some(&format!("{} Sprite Effect Shader", name)),
+                source: wgpu::ShaderSource::Wgsl(source.into()),
+            });
+
+            pipelines.push(StencilPipelines::new(
+                device,
+                &pipeline_layout,
+                &shader,
+                &[wgpu::VertexBufferLayout {
+                    array_stride: 4 * 4,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &wgpu::vertex_attr_array![
+                        0 => Float32x2,
+                        1 => Float32x2,
+                    ],
+                }],
+                surface_format,
+                sample_count,
+                &format!("{} Sprite Effect Render Pipeline", name),
+            ));
+        }
+
+        let params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Sprite Effect Params Buffer"),
+            size: std::mem::size_of::<EffectParams>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let empty_palette = device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some("Sprite Effect Empty Palette"),
+                size: wgpu::Extent3d {
+                    width: 1,
+                    height: 1,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba8Unorm,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            })
+            .create_view(&wgpu::TextureViewDescriptor::default());
+
+        Self {
+            params_bind_group_layout,
+            pipelines,
+            params_buffer,
+            empty_palette,
+        }
+    }
+
+    pub fn pipeline(&self, effect: &SpriteEffect, stencil: StencilMode) -> &RenderPipeline {
+        self.pipelines[effect.index()].get(stencil)
+    }
+}
+
+impl<S> Gamma<S> {
+    /// Create a palette for `SpriteEffect::PaletteSwap`, with one row for each list of colors.
+    ///
+    /// Rows shorter than the longest one are filled up with transparent colors.
+    pub fn create_palette(&self, palettes: &[&[Color]]) -> Result<Texture, GammaError> {
+        let width = palettes
+            .iter()
+            .map(|colors| colors.len())
+            .max()
+            .unwrap_or(0);
+
+        let mut pixels = Vec::with_capacity(width * palettes.len() * 4);
+        for colors in palettes {
+            for i in 0..width {
+                let color = colors.get(i).copied().unwrap_or(Color::TRANSPARENT);
+                pixels.extend_from_slice(&color.to_rgba8());
+            }
+        }
+
+        self.create_texture_from_rgba(width as u32, palettes.len() as u32, &pixels)
+    }
+
+    /// Draw a texture with a built-in effect such as a hit flash or an outline.
+    pub fn draw_texture_with_effect(
+        &mut self,
+        texture: &Texture,
+        x: f32,
+        y: f32,
+        flip: Flip,
+        effect: SpriteEffect,
+    ) {
+        self.draw_texture_scaled_with_effect(
+            texture,
+            x,
+            y,
+            texture.width as f32,
+            texture.height as f32,
+            flip,
+            effect,
+        );
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_texture_scaled_with_effect(
+        &mut self,
+        texture: &Texture,
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        flip: Flip,
+        effect: SpriteEffect,
+    ) {
+        if !self.acquire_frame() {
+            return;
+        }
+
+        let (device, queue, texture_pipeline, frame) = match (
+            self.device.as_ref(),
+            self.queue.as_ref(),
+            self.texture_pipeline.as_ref(),
+            self.current_frame.as_ref(),
+        ) {
+            (Some(d), Some(q), Some(t), Some(f)) => (d, q, t, f),
+            _ => return,
+        };
+
+        let Some(scissor) = self.current_scissor(frame) else {
+            return;
+        };
+        let stencil = self.stencil_mode();
+        let view = self.view();
+
+        let sample_count = self.sample_count;
+        let pipeline = self.sprite_effect_pipeline.get_or_insert_with(|| {
+            SpriteEffectPipeline::new(
+                device,
+                frame.texture.format(),
+                &texture_pipeline.bind_group_layout,
+                sample_count,
+            )
+        });
+
+        let (x, y) = view.point(x, y);
+        let (width, height) = (view.length(width), view.length(height));
+        let vertices = sprite_vertices(frame.width(), frame.height(), x, y, width, height, flip);
+
+        // Outlines reach past the edges of the texture, so the quad grows to make room for them
+        // and its texture coordinates reach past 0.0 and 1.0 by as much.
+        let mut quad = vertices;
+        if let SpriteEffect::Outline { width: outline, .. } = effect {
+            let padding = outline_width(outline).ceil();
+            let (pad_u, pad_v) = (
+                padding / texture.width as f32,
+                padding / texture.height as f32,
+            );
+            let (pad_x, pad_y) = (pad_u * width, pad_v * height);
+
+            quad = sprite_vertices(
+                frame.width(),
+                frame.height(),
+                x - pad_x,
+                y - pad_y,
+                width + pad_x * 2.0,
+                height + pad_y * 2.0,
+                flip,
+            );
+            for vertex in &mut quad {
+                vertex.tex_coords = [
+                    vertex.tex_coords[0] * (1.0 + pad_u * 2.0) - pad_u,
+                    vertex.tex_coords[1] * (1.0 + pad_v * 2.0) - pad_v,
+                ];
+            }
+        }
+
+        let indices: [u16; 6] = [0, 1, 2, 1, 3, 2];
+
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Sprite Effect Vertex Buffer"),
+            contents: bytemuck::cast_slice(&quad),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Sprite Effect Index Buffer"),
+            contents: bytemuck::cast_slice(&indices),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+
+        queue.write_buffer(
+            &pipeline.params_buffer,
+            0,
+            bytemuck::bytes_of(&effect.params(texture)),
+        );
+
+        let texture_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Sprite Effect Texture Bind Group"),
+            layout: &texture_pipeline.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&texture.sampler),
+                },
+            ],
+        });
+
+        let palette = match effect {
+            SpriteEffect::PaletteSwap { palette, .. } => &palette.view,
+            _ => &pipeline.empty_palette,
+        };
+
+        let params_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Sprite Effect Params Bind Group"),
+            layout: &pipeline.params_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: pipeline.params_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(palette),
+                },
+            ],
+        });
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Sprite Effect Render Encoder"),
+        });
+
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Sprite Effect Render Pass"),
+                color_attachments: &[Some(frame.color_attachment(wgpu::LoadOp::Load))],
+                depth_stencil_attachment: frame.depth_stencil_attachment(),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+                multiview_mask: None,
+            });
+
+            scissor.apply(&mut render_pass);
+            stencil.apply(&mut render_pass);
+            render_pass.set_pipeline(pipeline.pipeline(&effect, stencil));
+            render_pass.set_bind_group(0, &texture_bind_group, &[]);
+            render_pass.set_bind_group(1, &params_bind_group, &[]);
+            render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+            render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+            render_pass.draw_indexed(0..6, 0, 0..1);
+        }
+
+        queue.submit(std::iter::once(encoder.finish()));
+
+        self.frame_stats.batches += 1;
+        self.frame_stats.draw_calls += 1;
+        self.frame_stats.vertices += quad.len() as u32;
+        self.frame_stats.texture_binds += match effect {
+            SpriteEffect::PaletteSwap { .. } => 2,
+            _ => 1,
+        };
+        self.frame_stats.buffer_uploads += 3;
+
+        // The lights only need the sprite itself, not the room made for an outline.
+        self.draw_sprite_normals(texture, None, &vertices, flip, scissor);
+    }
+}
@@ -0,0 +1,101 @@
+use std::time::Duration;
+
+use crate::{gamma::Gamma, rendering::text::TextRenderer};
+
+/// Counters describing the work done to render a single frame.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RenderStats {
+    /// The number of draw calls issued to the GPU.
+    pub draw_calls: u32,
+    /// The number of render passes, each one is submitted to the GPU separately.
+    pub batches: u32,
+    /// The number of vertices drawn.
+    pub vertices: u32,
+    /// The number of times a texture was bound for drawing.
+    pub texture_binds: u32,
+    /// The number of buffers and texture regions uploaded to the GPU.
+    pub buffer_uploads: u32,
+    /// CPU time spent in the update function.
+    pub update_time: Duration,
+    /// CPU time spent in the draw function, including the engine's own drawing work.
+    pub draw_time: Duration,
+    /// The time since the previous frame.
+    pub frame_time: Duration,
+}
+
+const OVERLAY_TEXT_SIZE: f32 = 20.0;
+const OVERLAY_MARGIN: f32 = 10.0;
+
+impl<S> Gamma<S> {
+    /// Get the render statistics of the last completed frame.
+    pub fn render_stats(&self) -> RenderStats {
+        self.render_stats
+    }
+
+    /// Show or hide the built in overlay that displays the render statistics.
+    pub fn set_stats_overlay(&mut self, show: bool) {
+        self.show_stats_overlay = show;
+    }
+
+    /// Record the statistics for the frame that just finished and draw the overlay.
+    pub(crate) fn finish_frame_stats(&mut self) {
+        self.render_stats = self.frame_stats;
+
+        if self.show_stats_overlay {
+            self.draw_stats_overlay();
+        }
+
+        self.frame_stats = RenderStats::default();
+    }
+
+    fn draw_stats_overlay(&mut self) {
+        if !self.acquire_frame() {
+            return;
+        }
+
+        let sample_count = self.sample_count;
+
+        let (device, queue, frame) = match (
+            self.device.as_ref(),
+            self.queue.as_ref(),
+            self.current_frame.as_ref(),
+        ) {
+            (Some(d), Some(q), Some(f)) => (d, q, f),
+            _ => return,
+        };
+
+        let text_renderer = self
+            .text_renderer
+            .get_or_insert_with(|| TextRenderer::new(device, frame.texture.format(), sample_count));
+
+        let stats = self.render_stats;
+        let fps = if stats.frame_time.is_zero() {
+            0.0
+        } else {
+            1.0 / stats.frame_time.as_secs_f64()
+        };
+
+        let lines = [
+            format!(
+                "{:.0} fps ({:.2} ms)",
+                fps,
+                stats.frame_time.as_secs_f64() * 1000.0
+            ),
+            format!("update: {:.2} ms", stats.update_time.as_secs_f64() * 1000.0),
+            format!("draw: {:.2} ms", stats.draw_time.as_secs_f64() * 1000.0),
+            format!("draw calls: {}", stats.draw_calls),
+            format!("batches: {}", stats.batches),
+            format!("vertices: {}", stats.vertices),
+            format!("texture binds: {}", stats.texture_binds),
+            format!("buffer uploads: {}", stats.buffer_uploads),
+        ];
+
+        for (i, line) in lines.iter().enumerate() {
+            let x = OVERLAY_MARGIN;
+            let y = OVERLAY_MARGIN + i as f32 * OVERLAY_TEXT_SIZE;
+            text_renderer.queue_with_shadow(line, x, y, OVERLAY_TEXT_SIZE, [1.0, 1.0, 1.0, 1.0]);
+        }
+
+        text_renderer.draw(device, queue, frame, &mut self.frame_stats);
+    }
+}
@@ -0,0 +1,273 @@
+use std::cell::Cell;
+
+use wgpu::{PipelineLayout, RenderPipeline, ShaderModule, TextureView};
+
+use crate::{
+    gamma::Gamma,
+    rendering::{msaa::multisample_state, pipeline::FRAME_BLEND},
+};
+
+/// The format of the mask buffer attached to every frame pass. Only the stencil part is used.
+pub const STENCIL_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth24PlusStencil8;
+
+/// The stencil value written by the mask and tested against when drawing masked content.
+const MASK_REFERENCE: u32 = 1;
+
+/// Which part of the mask later drawing is limited to.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum MaskMode {
+    /// Draw everywhere, ignoring the mask.
+    Disabled,
+    /// Only draw where the mask has been drawn.
+    Inside,
+    /// Only draw where the mask has not been drawn.
+    Outside,
+}
+
+/// How a pipeline uses the stencil buffer. Every pipeline that draws into a frame pass
+/// is created once for each of these.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub(crate) enum StencilMode {
+    Ignore,
+    Write,
+    Inside,
+    Outside,
+}
+
+impl StencilMode {
+    pub const ALL: [StencilMode; 4] = [
+        StencilMode::Ignore,
+        StencilMode::Write,
+        StencilMode::Inside,
+        StencilMode::Outside,
+    ];
+
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    pub fn depth_stencil_state(self) -> wgpu::DepthStencilState {
+        let (compare, pass_op, write_mask) = match self {
+            StencilMode::Ignore => (
+                wgpu::CompareFunction::Always,
+                wgpu::StencilOperation::Keep,
+                0,
+            ),
+            StencilMode::Write => (
+                wgpu::CompareFunction::Always,
+                wgpu::StencilOperation::Replace,
+                0xff,
+            ),
+            StencilMode::Inside => (
+                wgpu::CompareFunction::Equal,
+                wgpu::StencilOperation::Keep,
+                0,
+            ),
+            StencilMode::Outside => (
+                wgpu::CompareFunction::NotEqual,
+                wgpu::StencilOperation::Keep,
+                0,
+            ),
+        };
+
+        let face = wgpu::StencilFaceState {
+            compare,
+            fail_op: wgpu::StencilOperation::Keep,
+            depth_fail_op: wgpu::StencilOperation::Keep,
+            pass_op,
+        };
+
+        wgpu::DepthStencilState {
+            format: STENCIL_FORMAT,
+            depth_write_enabled: false,
+            depth_compare: wgpu::CompareFunction::Always,
+            stencil: wgpu::StencilState {
+                front: face,
+                back: face,
+                read_mask: 0xff,
+                write_mask,
+            },
+            bias: wgpu::DepthBiasState::default(),
+        }
+    }
+
+    /// Drawing into the mask only touches the stencil buffer, never the frame's colors.
+    pub fn color_writes(self) -> wgpu::ColorWrites {
+        match self {
+            StencilMode::Write => wgpu::ColorWrites::empty(),
+            _ => wgpu::ColorWrites::ALL,
+        }
+    }
+
+    /// Set the value the mask is written with or tested against. Drawing that ignores the mask
+    /// leaves the render pass as it is.
+    pub fn apply(self, render_pass: &mut wgpu::RenderPass) {
+        if self != StencilMode::Ignore {
+            render_pass.set_stencil_reference(MASK_REFERENCE);
+        }
+    }
+}
+
+/// One pipeline for each `StencilMode`, drawing triangle lists into a frame pass.
+///
+/// The shader needs a `vs_main` vertex entry point, `fs_main` for drawing and `fs_mask` for
+/// writing the mask.
+pub(crate) struct StencilPipelines {
+    pipelines: Vec<RenderPipeline>,
+}
+
+impl StencilPipelines {
+    pub fn new(
+        device: &wgpu::Device,
+        layout: &PipelineLayout,
+        shader: &ShaderModule,
+        buffers: &[wgpu::VertexBufferLayout],
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+        label: &str,
+    ) -> Self {
+        let create_pipeline = |stencil: StencilMode| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(layout),
+                vertex: wgpu::VertexState {
+                    module: shader,
+                    entry_point: Some("vs_main"),
+                    buffers,
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: shader,
+                    entry_point: Some(match stencil {
+                        StencilMode::Write => "fs_mask",
+                        _ => "fs_main",
+                    }),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format,
+                        blend: Some(FRAME_BLEND),
+                        write_mask: stencil.color_writes(),
+                    })],
+                    compilation_options: Default::default(),
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    ..Default::default()
+                },
+                depth_stencil: Some(stencil.depth_stencil_state()),
+                multisample: multisample_state(sample_count),
+                multiview_mask: None,
+                cache: None,
+            })
+        };
+
+        Self {
+            pipelines: StencilMode::ALL.into_iter().map(create_pipeline).collect(),
+        }
+    }
+
+    pub fn get(&self, stencil: StencilMode) -> &RenderPipeline {
+        &self.pipelines[stencil.index()]
+    }
+}
+
+/// The mask buffer for the frame, cleared the first time a pass uses it.
+pub(crate) struct FrameStencil {
+    pub view: TextureView,
+    pub cleared: Cell<bool>,
+}
+
+impl FrameStencil {
+    pub fn attachment(&self) -> wgpu::RenderPassDepthStencilAttachment<'_> {
+        let load = if self.cleared.replace(true) {
+            wgpu::LoadOp::Load
+        } else {
+            wgpu::LoadOp::Clear(0)
+        };
+
+        wgpu::RenderPassDepthStencilAttachment {
+            view: &self.view,
+            depth_ops: None,
+            stencil_ops: Some(wgpu::Operations {
+                load,
+                store: wgpu::StoreOp::Store,
+            }),
+        }
+    }
+}
+
+impl<S> Gamma<S> {
+    /// Start drawing into the mask. Until `end_mask` is called, anything drawn marks the mask
+    /// wherever it is not transparent instead of appearing on screen.
+    pub fn begin_mask(&mut self) {
+        self.writing_mask = true;
+    }
+
+    /// Stop drawing into the mask, going back to drawing to the screen.
+    pub fn end_mask(&mut self) {
+        self.writing_mask = false;
+    }
+
+    /// Limit later drawing to inside or outside of the mask, or turn masking off.
+    pub fn set_mask_mode(&mut self, mode: MaskMode) {
+        self.mask_mode = mode;
+    }
+
+    /// Erase everything drawn into the mask. The mask also starts out empty every frame.
+    pub fn clear_mask(&mut self) {
+        if let Some(frame) = self.current_frame.as_ref() {
+            frame.stencil.cleared.set(false);
+        }
+    }
+
+    /// The stencil mode for the next draw, from the current mask settings.
+    pub(crate) fn stencil_mode(&self) -> StencilMode {
+        if self.writing_mask {
+            return StencilMode::Write;
+        }
+
+        match self.mask_mode {
+            MaskMode::Disabled => StencilMode::Ignore,
+            MaskMode::Inside => StencilMode::Inside,
+            MaskMode::Outside => StencilMode::Outside,
+        }
+    }
+
+    /// The mask buffer for a frame, recreated whenever the frame size changes.
+    pub(crate) fn frame_stencil(&mut self, width: u32, height: u32) -> Option<FrameStencil> {
+        let device = self.device.as_ref()?;
+
+        let matches = self.stencil_texture.as_ref().is_some_and(|texture| {
+            texture.width() == width
+                && texture.height() == height
+                && texture.sample_count() == self.sample_count
+        });
+
+        if !matches {
+            self.stencil_texture = Some(device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("Mask Stencil Texture"),
+                size: wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                // The mask is attached alongside the frame's color target, so their sample counts match.
+                sample_count: self.sample_count,
+                dimension: wgpu::TextureDimension::D2,
+                format: STENCIL_FORMAT,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                view_formats: &[],
+            }));
+        }
+
+        let view = self
+            .stencil_texture
+            .as_ref()?
+            .create_view(&wgpu::TextureViewDescriptor::default());
+
+        Some(FrameStencil {
+            view,
+            cleared: Cell::new(false),
+        })
+    }
+}
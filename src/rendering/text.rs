@@ -0,0 +1,406 @@
+use glyph_brush::{
+    BrushAction, BrushError, GlyphBrush, GlyphBrushBuilder, Section, Text, ab_glyph::FontArc,
+};
+use wgpu::{BindGroup, BindGroupLayout, RenderPipeline, Sampler, util::DeviceExt};
+
+use crate::rendering::{RenderStats, frame::Frame, msaa::multisample_state, pipeline::FRAME_BLEND};
+
+/// The font used for engine drawn text such as the stats overlay.
+const BUILTIN_FONT: &[u8] = include_bytes!("../../assets/fonts/KOMIKAP_.ttf");
+
+const INITIAL_CACHE_SIZE: u32 = 256;
+
+const TEXT_SHADER: &str = r#"
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+    @location(1) tex_coords: vec2<f32>,
+    @location(2) color: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) color: vec4<f32>,
+}
+
+@vertex
+fn vs_main(input: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(input.position, 0.0, 1.0);
+    out.tex_coords = input.tex_coords;
+    out.color = input.color;
+    return out;
+}
+
+@group(0) @binding(0)
+var t_glyphs: texture_2d<f32>;
+@group(0) @binding(1)
+var s_glyphs: sampler;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let coverage = textureSample(t_glyphs, s_glyphs, in.tex_coords).r;
+    return vec4<f32>(in.color.rgb, in.color.a * coverage);
+}
+"#;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct TextVertex {
+    position: [f32; 2],
+    tex_coords: [f32; 2],
+    color: [f32; 4],
+}
+
+/// Draws simple screen space text with the built in font, rasterized through `glyph_brush`.
+pub struct TextRenderer {
+    brush: GlyphBrush<[TextVertex; 4]>,
+    bind_group_layout: BindGroupLayout,
+    pipeline: RenderPipeline,
+    sampler: Sampler,
+    cache_texture: wgpu::Texture,
+    bind_group: BindGroup,
+    /// Glyph quads in pixel coordinates, kept so unchanged text can be redrawn.
+    quads: Vec<[TextVertex; 4]>,
+}
+
+impl TextRenderer {
+    pub fn new(
+        device: &wgpu::Device,
+        surface_format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> Self {
+        let font = FontArc::try_from_slice(BUILTIN_FONT).expect("built in font is valid");
+        let brush = GlyphBrushBuilder::using_font(font)
+            .initial_cache_size((INITIAL_CACHE_SIZE, INITIAL_CACHE_SIZE))
+            .build();
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Text Shader"),
+            source: wgpu::ShaderSource::Wgsl(TEXT_SHADER.into()),
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Text Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Text Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            immediate_size: 0,
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Text Render Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<TextVertex>() as wgpu::BufferAddress,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &wgpu::vertex_attr_array![
+                        0 => Float32x2,
+                        1 => Float32x2,
+                        2 => Float32x4,
+                    ],
+                }],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: surface_format,
+                    blend: Some(FRAME_BLEND),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: multisample_state(sample_count),
+            multiview_mask: None,
+            cache: None,
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Text Sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let (cache_texture, bind_group) = create_cache(
+            device,
+            &bind_group_layout,
+            &sampler,
+            INITIAL_CACHE_SIZE,
+            INITIAL_CACHE_SIZE,
+        );
+
+        Self {
+            brush,
+            bind_group_layout,
+            pipeline,
+            sampler,
+            cache_texture,
+            bind_group,
+            quads: Vec::new(),
+        }
+    }
+
+    /// Queue text to be drawn at a pixel position on the next call to `draw`.
+    pub fn queue(&mut self, text: &str, x: f32, y: f32, size: f32, color: [f32; 4]) {
+        self.brush.queue(
+            Section::default()
+                .with_screen_position((x, y))
+                .add_text(Text::new(text).with_scale(size).with_color(color)),
+        );
+    }
+
+    /// Queue text with a black drop shadow, which keeps it readable on any background.
+    pub fn queue_with_shadow(&mut self, text: &str, x: f32, y: f32, size: f32, color: [f32; 4]) {
+        self.queue(text, x + 1.0, y + 1.0, size, [0.0, 0.0, 0.0, color[3]]);
+        self.queue(text, x, y, size, color);
+    }
+
+    /// Draw all queued text on top of the frame.
+    pub fn draw(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        frame: &Frame,
+        stats: &mut RenderStats,
+    ) {
+        let action = loop {
+            let cache_texture = &self.cache_texture;
+            let result = self.brush.process_queued(
+                |rect, data| {
+                    stats.buffer_uploads += 1;
+                    queue.write_texture(
+                        wgpu::TexelCopyTextureInfo {
+                            texture: cache_texture,
+                            mip_level: 0,
+                            origin: wgpu::Origin3d {
+                                x: rect.min[0],
+                                y: rect.min[1],
+                                z: 0,
+                            },
+                            aspect: wgpu::TextureAspect::All,
+                        },
+                        data,
+                        wgpu::TexelCopyBufferLayout {
+                            offset: 0,
+                            bytes_per_row: Some(rect.width()),
+                            rows_per_image: Some(rect.height()),
+                        },
+                        wgpu::Extent3d {
+                            width: rect.width(),
+                            height: rect.height(),
+                            depth_or_array_layers: 1,
+                        },
+                    );
+                },
+                to_quad,
+            );
+
+            match result {
+                Ok(action) => break action,
+                Err(BrushError::TextureTooSmall { suggested }) => {
+                    let max = device.limits().max_texture_dimension_2d;
+                    let (width, height) = (suggested.0.min(max), suggested.1.min(max));
+
+                    if (width, height) == self.brush.texture_dimensions() {
+                        eprintln!(
+                            "Skipped drawing text, its glyphs do not fit in a {}x{} glyph cache",
+                            width, height
+                        );
+                        // The brush keeps the queue after an error, rebuilding it drops the text.
+                        self.brush = self
+                            .brush
+                            .to_builder()
+                            .initial_cache_size((width, height))
+                            .build();
+                        self.quads.clear();
+                        return;
+                    }
+
+                    let (cache_texture, bind_group) = create_cache(
+                        device,
+                        &self.bind_group_layout,
+                        &self.sampler,
+                        width,
+                        height,
+                    );
+                    self.cache_texture = cache_texture;
+                    self.bind_group = bind_group;
+                    self.brush.resize_texture(width, height);
+                }
+            }
+        };
+
+        if let BrushAction::Draw(quads) = action {
+            self.quads = quads;
+        }
+
+        if self.quads.is_empty() {
+            return;
+        }
+
+        let (width, height) = (frame.width(), frame.height());
+        let vertices: Vec<TextVertex> = self
+            .quads
+            .iter()
+            .flatten()
+            .map(|vertex| TextVertex {
+                position: [
+                    (vertex.position[0] / width) * 2.0 - 1.0,
+                    1.0 - (vertex.position[1] / height) * 2.0,
+                ],
+                ..*vertex
+            })
+            .collect();
+
+        let indices: Vec<u32> = (0..self.quads.len() as u32)
+            .flat_map(|quad| {
+                let base = quad * 4;
+                [base, base + 1, base + 2, base + 1, base + 3, base + 2]
+            })
+            .collect();
+
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Text Vertex Buffer"),
+            contents: bytemuck::cast_slice(&vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Text Index Buffer"),
+            contents: bytemuck::cast_slice(&indices),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Text Render Encoder"),
+        });
+
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Text Render Pass"),
+                color_attachments: &[Some(frame.color_attachment(wgpu::LoadOp::Load))],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+                multiview_mask: None,
+            });
+
+            render_pass.set_pipeline(&self.pipeline);
+            render_pass.set_bind_group(0, &self.bind_group, &[]);
+            render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+            render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+            render_pass.draw_indexed(0..indices.len() as u32, 0, 0..1);
+        }
+
+        queue.submit(std::iter::once(encoder.finish()));
+
+        stats.batches += 1;
+        stats.draw_calls += 1;
+        stats.texture_binds += 1;
+        stats.buffer_uploads += 2;
+        stats.vertices += vertices.len() as u32;
+    }
+}
+
+/// Turn a positioned glyph into a quad in pixel coordinates.
+fn to_quad(glyph: glyph_brush::GlyphVertex) -> [TextVertex; 4] {
+    let pixels = glyph.pixel_coords;
+    let uv = glyph.tex_coords;
+    let color = glyph.extra.color;
+
+    [
+        TextVertex {
+            position: [pixels.min.x, pixels.min.y],
+            tex_coords: [uv.min.x, uv.min.y],
+            color,
+        },
+        TextVertex {
+            position: [pixels.max.x, pixels.min.y],
+            tex_coords: [uv.max.x, uv.min.y],
+            color,
+        },
+        TextVertex {
+            position: [pixels.min.x, pixels.max.y],
+            tex_coords: [uv.min.x, uv.max.y],
+            color,
+        },
+        TextVertex {
+            position: [pixels.max.x, pixels.max.y],
+            tex_coords: [uv.max.x, uv.max.y],
+            color,
+        },
+    ]
+}
+
+fn create_cache(
+    device: &wgpu::Device,
+    layout: &BindGroupLayout,
+    sampler: &Sampler,
+    width: u32,
+    height: u32,
+) -> (wgpu::Texture, BindGroup) {
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Glyph Cache Texture"),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::R8Unorm,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        view_formats: &[],
+    });
+
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Glyph Cache Bind Group"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(sampler),
+            },
+        ],
+    });
+
+    (texture, bind_group)
+}
@@ -2,9 +2,14 @@
 
 use wgpu::{Sampler, TextureView, util::DeviceExt};
 
-use crate::{gamma::Gamma, rendering::Frame};
+use crate::{
+    error::GammaError,
+    gamma::Gamma,
+    rendering::mipmap::{MIPMAP_FORMAT, mip_level_count},
+};
 
 pub struct Texture {
+    pub(crate) texture: wgpu::Texture,
     pub(crate) view: TextureView,
     pub(crate) sampler: Sampler,
     pub width: u32,
@@ -25,23 +30,199 @@ pub enum Flip {
 
 impl<S> Gamma<S> {
     // Load from file path
-    pub fn load_texture(&self, path: &str) -> Result<Texture, String> {
-        let img_bytes = std::fs::read(path)
-            .map_err(|e| format!("Failed to read image file '{}': {}", path, e))?;
+    pub fn load_texture(&self, path: &str) -> Result<Texture, GammaError> {
+        let img_bytes = std::fs::read(path).map_err(|e| GammaError::io(path, e))?;
 
         self.load_texture_from_bytes(&img_bytes)
     }
 
     // Load from bytes (works with include_bytes!)
-    pub fn load_texture_from_bytes(&self, bytes: &[u8]) -> Result<Texture, String> {
-        let device = self.device.as_ref().ok_or("Device not initialized")?;
-        let queue = self.queue.as_ref().ok_or("Queue not initialized")?;
+    pub fn load_texture_from_bytes(&self, bytes: &[u8]) -> Result<Texture, GammaError> {
+        self.create_texture_from_image_bytes(bytes, false, MIPMAP_FORMAT)
+    }
+
+    /// Load from file path and generate the full mip chain, so the texture
+    /// stays smooth when drawn smaller than its original size.
+    pub fn load_texture_with_mipmaps(&self, path: &str) -> Result<Texture, GammaError> {
+        let img_bytes = std::fs::read(path).map_err(|e| GammaError::io(path, e))?;
+
+        self.load_texture_from_bytes_with_mipmaps(&img_bytes)
+    }
+
+    /// Load from bytes and generate the full mip chain, so the texture
+    /// stays smooth when drawn smaller than its original size.
+    pub fn load_texture_from_bytes_with_mipmaps(
+        &self,
+        bytes: &[u8],
+    ) -> Result<Texture, GammaError> {
+        self.create_texture_from_image_bytes(bytes, true, MIPMAP_FORMAT)
+    }
+
+    /// Load a normal map from file path for use with `draw_texture_with_normal_map`.
+    pub fn load_normal_map(&self, path: &str) -> Result<Texture, GammaError> {
+        let img_bytes = std::fs::read(path).map_err(|e| GammaError::io(path, e))?;
+
+        self.load_normal_map_from_bytes(&img_bytes)
+    }
+
+    /// Load a normal map from bytes for use with `draw_texture_with_normal_map`.
+    ///
+    /// Normal maps hold directions rather than colors, so they are stored without sRGB conversion.
+    /// They are expected to use the common "green up" convention.
+    pub fn load_normal_map_from_bytes(&self, bytes: &[u8]) -> Result<Texture, GammaError> {
+        self.create_texture_from_image_bytes(bytes, false, wgpu::TextureFormat::Rgba8Unorm)
+    }
+
+    /// Create a fully transparent texture that can be changed later with `update_texture_region`.
+    pub fn create_texture(&self, width: u32, height: u32) -> Result<Texture, GammaError> {
+        self.check_texture_size(width, height)?;
+        let pixels = vec![0; width as usize * height as usize * 4];
+        self.create_texture_from_rgba(width, height, &pixels)
+    }
+
+    /// Create a texture from raw RGBA pixels, four bytes per pixel in rows from top to bottom.
+    pub fn create_texture_from_rgba(
+        &self,
+        width: u32,
+        height: u32,
+        pixels: &[u8],
+    ) -> Result<Texture, GammaError> {
+        self.create_texture_from_pixels((width, height), pixels, false, MIPMAP_FORMAT)
+    }
+
+    /// Replace a rectangle of a texture with raw RGBA pixels, four bytes per pixel in rows from top to bottom.
+    ///
+    /// The change is visible to everything drawn after this call.
+    pub fn update_texture_region(
+        &mut self,
+        texture: &Texture,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        pixels: &[u8],
+    ) -> Result<(), GammaError> {
+        let queue = self
+            .queue
+            .as_ref()
+            .ok_or(GammaError::NotInitialized("Queue"))?;
+
+        if x.checked_add(width)
+            .is_none_or(|right| right > texture.width)
+            || y.checked_add(height)
+                .is_none_or(|bottom| bottom > texture.height)
+        {
+            return Err(GammaError::InvalidInput(format!(
+                "Region {}x{} at ({}, {}) is outside the {}x{} texture",
+                width, height, x, y, texture.width, texture.height
+            )));
+        }
+
+        let expected = width as usize * height as usize * 4;
+        if pixels.len() != expected {
+            return Err(GammaError::InvalidInput(format!(
+                "Expected {} bytes of RGBA pixels for a {}x{} region but got {}",
+                expected,
+                width,
+                height,
+                pixels.len()
+            )));
+        }
+
+        if width == 0 || height == 0 {
+            return Ok(());
+        }
+
+        queue.write_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture: &texture.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d { x, y, z: 0 },
+                aspect: wgpu::TextureAspect::All,
+            },
+            pixels,
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * width),
+                rows_per_image: Some(height),
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        self.frame_stats.buffer_uploads += 1;
+
+        Ok(())
+    }
 
+    fn create_texture_from_image_bytes(
+        &self,
+        bytes: &[u8],
+        generate_mipmaps: bool,
+        format: wgpu::TextureFormat,
+    ) -> Result<Texture, GammaError> {
         let img = image::load_from_memory(bytes)
-            .map_err(|e| format!("Failed to decode image: {}", e))?
+            .map_err(|e| GammaError::Decode(format!("Failed to decode image: {}", e)))?
             .to_rgba8();
 
-        let dimensions = img.dimensions();
+        self.create_texture_from_pixels(img.dimensions(), &img, generate_mipmaps, format)
+    }
+
+    /// Make sure the device can create a texture of this size, instead of letting wgpu panic.
+    fn check_texture_size(&self, width: u32, height: u32) -> Result<(), GammaError> {
+        let device = self
+            .device
+            .as_ref()
+            .ok_or(GammaError::NotInitialized("Device"))?;
+
+        if width == 0 || height == 0 {
+            return Err(GammaError::InvalidInput(
+                "Texture width and height must be greater than zero".to_string(),
+            ));
+        }
+
+        let max = device.limits().max_texture_dimension_2d;
+        if width > max || height > max {
+            return Err(GammaError::InvalidInput(format!(
+                "A {}x{} texture is larger than the {}x{} the device supports",
+                width, height, max, max
+            )));
+        }
+
+        Ok(())
+    }
+
+    fn create_texture_from_pixels(
+        &self,
+        dimensions: (u32, u32),
+        pixels: &[u8],
+        generate_mipmaps: bool,
+        format: wgpu::TextureFormat,
+    ) -> Result<Texture, GammaError> {
+        let device = self
+            .device
+            .as_ref()
+            .ok_or(GammaError::NotInitialized("Device"))?;
+        let queue = self
+            .queue
+            .as_ref()
+            .ok_or(GammaError::NotInitialized("Queue"))?;
+
+        self.check_texture_size(dimensions.0, dimensions.1)?;
+
+        let expected = dimensions.0 as usize * dimensions.1 as usize * 4;
+        if pixels.len() != expected {
+            return Err(GammaError::InvalidInput(format!(
+                "Expected {} bytes of RGBA pixels for a {}x{} texture but got {}",
+                expected,
+                dimensions.0,
+                dimensions.1,
+                pixels.len()
+            )));
+        }
 
         let texture_size = wgpu::Extent3d {
             width: dimensions.0,
@@ -49,14 +230,26 @@ impl<S> Gamma<S> {
             depth_or_array_layers: 1,
         };
 
+        let mip_level_count = if generate_mipmaps {
+            mip_level_count(dimensions.0, dimensions.1)
+        } else {
+            1
+        };
+
+        let mut usage = wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST;
+        if mip_level_count > 1 {
+            // Each level is rendered from the one above it.
+            usage |= wgpu::TextureUsages::RENDER_ATTACHMENT;
+        }
+
         let texture = device.create_texture(&wgpu::TextureDescriptor {
             label: Some("Loaded Texture"),
             size: texture_size,
-            mip_level_count: 1,
+            mip_level_count,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8UnormSrgb,
-            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            format,
+            usage,
             view_formats: &[],
         });
 
@@ -67,7 +260,7 @@ impl<S> Gamma<S> {
                 origin: wgpu::Origin3d::ZERO,
                 aspect: wgpu::TextureAspect::All,
             },
-            &img,
+            pixels,
             wgpu::TexelCopyBufferLayout {
                 offset: 0,
                 bytes_per_row: Some(4 * dimensions.0),
@@ -76,19 +269,34 @@ impl<S> Gamma<S> {
             texture_size,
         );
 
+        if mip_level_count > 1 {
+            let mipmap_pipeline = self
+                .mipmap_pipeline
+                .as_ref()
+                .ok_or(GammaError::NotInitialized("Mipmap pipeline"))?;
+            mipmap_pipeline.generate(device, queue, &texture, mip_level_count);
+        }
+
         let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
 
+        let mipmap_filter = if mip_level_count > 1 {
+            wgpu::MipmapFilterMode::Linear
+        } else {
+            wgpu::MipmapFilterMode::Nearest
+        };
+
         let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
             address_mode_u: wgpu::AddressMode::ClampToEdge,
             address_mode_v: wgpu::AddressMode::ClampToEdge,
             address_mode_w: wgpu::AddressMode::ClampToEdge,
             mag_filter: wgpu::FilterMode::Linear,
             min_filter: wgpu::FilterMode::Linear,
-            mipmap_filter: wgpu::MipmapFilterMode::Nearest,
+            mipmap_filter,
             ..Default::default()
         });
 
         Ok(Texture {
+            texture,
             view,
             sampler,
             width: dimensions.0,
@@ -117,73 +325,67 @@ impl<S> Gamma<S> {
         height: f32,
         flip: Flip,
     ) {
-        let (surface, device, queue, pipeline) = match (
-            self.surface.as_ref(),
+        self.draw_sprite(texture, None, x, y, width, height, flip);
+    }
+
+    /// Draw a texture that is shaded by the scene lights using a normal map.
+    ///
+    /// The normal map should be the same size as the texture and loaded with `load_normal_map`.
+    pub fn draw_texture_with_normal_map(
+        &mut self,
+        texture: &Texture,
+        normal_map: &Texture,
+        x: f32,
+        y: f32,
+        flip: Flip,
+    ) {
+        self.draw_sprite(
+            texture,
+            Some(normal_map),
+            x,
+            y,
+            texture.width as f32,
+            texture.height as f32,
+            flip,
+        );
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn draw_sprite(
+        &mut self,
+        texture: &Texture,
+        normal_map: Option<&Texture>,
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        flip: Flip,
+    ) {
+        if !self.acquire_frame() {
+            return;
+        }
+
+        let (device, queue, pipeline) = match (
             self.device.as_ref(),
             self.queue.as_ref(),
             self.texture_pipeline.as_ref(),
         ) {
-            (Some(s), Some(d), Some(q), Some(p)) => (s, d, q, p),
+            (Some(d), Some(q), Some(p)) => (d, q, p),
             _ => return,
         };
 
-        // Get or create current frame
-        if self.current_frame.is_none() {
-            let tex = match surface.get_current_texture() {
-                Ok(t) => t,
-                Err(e) => {
-                    eprintln!("Failed to get surface texture: {:?}", e);
-                    return;
-                }
-            };
-            self.current_frame = Some(Frame::new(tex));
-        }
-
         let frame = self.current_frame.as_ref().unwrap();
-        let window_size = self.window.as_ref().unwrap().inner_size();
-        let window_width = window_size.width as f32;
-        let window_height = window_size.height as f32;
-
-        let ndc_x = (x / window_width) * 2.0 - 1.0;
-        let ndc_y = 1.0 - (y / window_height) * 2.0;
-        let ndc_width = (width / window_width) * 2.0;
-        let ndc_height = (height / window_height) * 2.0;
-
-        #[repr(C)]
-        #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
-        struct Vertex {
-            position: [f32; 2],
-            tex_coords: [f32; 2],
-        }
-
-        let (u0, u1) = match flip {
-            Flip::Horizontal | Flip::Both => (1.0, 0.0),
-            _ => (0.0, 1.0),
+        let Some(scissor) = self.current_scissor(frame) else {
+            return;
         };
+        let stencil = self.stencil_mode();
+        let target_width = frame.width();
+        let target_height = frame.height();
 
-        let (v0, v1) = match flip {
-            Flip::Vertical | Flip::Both => (1.0, 0.0),
-            _ => (0.0, 1.0),
-        };
-
-        let vertices = [
-            Vertex {
-                position: [ndc_x, ndc_y],
-                tex_coords: [u0, v0],
-            },
-            Vertex {
-                position: [ndc_x + ndc_width, ndc_y],
-                tex_coords: [u1, v0],
-            },
-            Vertex {
-                position: [ndc_x, ndc_y - ndc_height],
-                tex_coords: [u0, v1],
-            },
-            Vertex {
-                position: [ndc_x + ndc_width, ndc_y - ndc_height],
-                tex_coords: [u1, v1],
-            },
-        ];
+        let view = self.view();
+        let (x, y) = view.point(x, y);
+        let (width, height) = (view.length(width), view.length(height));
+        let vertices = sprite_vertices(target_width, target_height, x, y, width, height, flip);
 
         let indices: [u16; 6] = [0, 1, 2, 1, 3, 2];
 
@@ -222,22 +424,16 @@ impl<S> Gamma<S> {
         {
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Texture Render Pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &frame.view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Load,
-                        store: wgpu::StoreOp::Store,
-                    },
-                    depth_slice: None,
-                })],
-                depth_stencil_attachment: None,
+                color_attachments: &[Some(frame.color_attachment(wgpu::LoadOp::Load))],
+                depth_stencil_attachment: frame.depth_stencil_attachment(),
                 timestamp_writes: None,
                 occlusion_query_set: None,
                 multiview_mask: None,
             });
 
-            render_pass.set_pipeline(&pipeline.pipeline);
+            scissor.apply(&mut render_pass);
+            stencil.apply(&mut render_pass);
+            render_pass.set_pipeline(pipeline.pipeline(stencil));
             render_pass.set_bind_group(0, &bind_group, &[]);
             render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
             render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint16);
@@ -245,5 +441,65 @@ impl<S> Gamma<S> {
         }
 
         queue.submit(std::iter::once(encoder.finish()));
+
+        self.frame_stats.batches += 1;
+        self.frame_stats.draw_calls += 1;
+        self.frame_stats.vertices += vertices.len() as u32;
+        self.frame_stats.texture_binds += 1;
+        self.frame_stats.buffer_uploads += 2;
+
+        self.draw_sprite_normals(texture, normal_map, &vertices, flip, scissor);
     }
 }
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct SpriteVertex {
+    pub position: [f32; 2],
+    pub tex_coords: [f32; 2],
+}
+
+/// Build the four corners of a sprite quad in normalized device coordinates.
+pub(crate) fn sprite_vertices(
+    target_width: f32,
+    target_height: f32,
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+    flip: Flip,
+) -> [SpriteVertex; 4] {
+    let ndc_x = (x / target_width) * 2.0 - 1.0;
+    let ndc_y = 1.0 - (y / target_height) * 2.0;
+    let ndc_width = (width / target_width) * 2.0;
+    let ndc_height = (height / target_height) * 2.0;
+
+    let (u0, u1) = match flip {
+        Flip::Horizontal | Flip::Both => (1.0, 0.0),
+        _ => (0.0, 1.0),
+    };
+
+    let (v0, v1) = match flip {
+        Flip::Vertical | Flip::Both => (1.0, 0.0),
+        _ => (0.0, 1.0),
+    };
+
+    [
+        SpriteVertex {
+            position: [ndc_x, ndc_y],
+            tex_coords: [u0, v0],
+        },
+        SpriteVertex {
+            position: [ndc_x + ndc_width, ndc_y],
+            tex_coords: [u1, v0],
+        },
+        SpriteVertex {
+            position: [ndc_x, ndc_y - ndc_height],
+            tex_coords: [u0, v1],
+        },
+        SpriteVertex {
+            position: [ndc_x + ndc_width, ndc_y - ndc_height],
+            tex_coords: [u1, v1],
+        },
+    ]
+}
@@ -0,0 +1,208 @@
+use crate::{
+    gamma::Gamma,
+    rendering::{clip::ClipRect, frame::Frame},
+};
+
+/// The smallest zoom a camera shows the world at. Zero or negative zoom would collapse or
+/// mirror the world.
+const MIN_ZOOM: f32 = 0.01;
+
+/// The part of the world shown in a `Viewport`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Camera {
+    /// The world position shown at the center of the viewport.
+    pub x: f32,
+    pub y: f32,
+    /// How many pixels on screen one world pixel takes up, at least 0.01.
+    pub zoom: f32,
+}
+
+impl Camera {
+    /// A camera centered on a world position.
+    pub fn new(x: f32, y: f32) -> Self {
+        Self { x, y, zoom: 1.0 }
+    }
+
+    pub fn with_zoom(mut self, zoom: f32) -> Self {
+        self.zoom = zoom.max(MIN_ZOOM);
+        self
+    }
+}
+
+/// A rectangle of the frame that the world is drawn into through its own camera, such as one
+/// player's half of a split screen.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Viewport {
+    /// The rectangle in frame pixels.
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub camera: Camera,
+    /// The player whose controller goes with this viewport.
+    pub player: usize,
+}
+
+impl Viewport {
+    /// A viewport for player 0 whose camera shows the world where it would be without one.
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+            camera: Camera::new(x + width / 2.0, y + height / 2.0),
+            player: 0,
+        }
+    }
+
+    pub fn with_camera(mut self, camera: Camera) -> Self {
+        self.camera = camera;
+        self
+    }
+
+    pub fn with_player(mut self, player: usize) -> Self {
+        self.player = player;
+        self
+    }
+
+    /// Split a frame into one viewport for each player, side by side for two players and in a
+    /// grid for more. Every viewport's camera starts out centered on the world origin.
+    pub fn split_screen(width: f32, height: f32, players: usize) -> Vec<Viewport> {
+        let columns = (players as f32).sqrt().ceil().max(1.0) as usize;
+        let rows = players.div_ceil(columns);
+        let (cell_width, cell_height) = (width / columns as f32, height / rows.max(1) as f32);
+
+        (0..players)
+            .map(|player| {
+                let (column, row) = (player % columns, player / columns);
+                Viewport::new(
+                    column as f32 * cell_width,
+                    row as f32 * cell_height,
+                    cell_width,
+                    cell_height,
+                )
+                .with_camera(Camera::new(0.0, 0.0))
+                .with_player(player)
+            })
+            .collect()
+    }
+
+    pub(crate) fn rect(&self) -> ClipRect {
+        ClipRect {
+            x: self.x,
+            y: self.y,
+            width: self.width.max(0.0),
+            height: self.height.max(0.0),
+        }
+    }
+
+    fn view(&self) -> View {
+        // The field is public, so it can be set below the minimum without `with_zoom`.
+        let zoom = self.camera.zoom.max(MIN_ZOOM);
+        View {
+            scale: zoom,
+            offset: [
+                self.x + self.width / 2.0 - self.camera.x * zoom,
+                self.y + self.height / 2.0 - self.camera.y * zoom,
+            ],
+        }
+    }
+}
+
+/// Turns world positions into frame pixels, as `frame = world * scale + offset`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct View {
+    pub scale: f32,
+    pub offset: [f32; 2],
+}
+
+impl View {
+    pub const IDENTITY: View = View {
+        scale: 1.0,
+        offset: [0.0, 0.0],
+    };
+
+    pub fn point(&self, x: f32, y: f32) -> (f32, f32) {
+        (
+            x * self.scale + self.offset[0],
+            y * self.scale + self.offset[1],
+        )
+    }
+
+    pub fn length(&self, length: f32) -> f32 {
+        length * self.scale
+    }
+
+    /// The `Screen` uniform of shaders that take positions in world pixels: the frame size and
+    /// the view offset, both in world pixels.
+    pub fn screen(&self, frame: &Frame) -> [f32; 4] {
+        [
+            frame.width() / self.scale,
+            frame.height() / self.scale,
+            self.offset[0] / self.scale,
+            self.offset[1] / self.scale,
+        ]
+    }
+}
+
+impl<S> Gamma<S> {
+    /// Draw everything after this call into a viewport through its camera, until `reset_viewport`.
+    ///
+    /// Drawing is limited to the viewport, and positions, sizes, clip rects, lights and debug
+    /// shapes are all in world pixels seen through the camera. `clear_screen` still clears the
+    /// whole frame.
+    pub fn set_viewport(&mut self, viewport: Viewport) {
+        self.viewport = Some(viewport);
+    }
+
+    /// Draw to the whole frame again, with positions in frame pixels.
+    pub fn reset_viewport(&mut self) {
+        self.viewport = None;
+    }
+
+    /// The viewport set with `set_viewport`, if any.
+    pub fn viewport(&self) -> Option<Viewport> {
+        self.viewport
+    }
+
+    /// Draw a scene once into every viewport, such as once per player in local co-op.
+    ///
+    /// `draw_scene` is called with each viewport in turn while it is set, so it can draw the
+    /// world the same way every time and use `viewport.player` for anything player specific.
+    pub fn draw_viewports(
+        &mut self,
+        viewports: &[Viewport],
+        mut draw_scene: impl FnMut(&mut Self, &Viewport),
+    ) {
+        // Clip rects belong to the viewport they were pushed in.
+        let clip_stack = std::mem::take(&mut self.clip_stack);
+
+        for viewport in viewports {
+            self.set_viewport(*viewport);
+            draw_scene(self, viewport);
+            self.clip_stack.clear();
+        }
+
+        self.reset_viewport();
+        self.clip_stack = clip_stack;
+    }
+
+    /// Split the frame into one viewport for each player, as with `Viewport::split_screen`.
+    /// Returns no viewports when there is nothing to draw to.
+    pub fn split_screen(&mut self, players: usize) -> Vec<Viewport> {
+        if !self.acquire_frame() {
+            return Vec::new();
+        }
+
+        let frame = self.current_frame.as_ref().unwrap();
+        Viewport::split_screen(frame.width(), frame.height(), players)
+    }
+
+    /// How world positions map to the frame in the current viewport.
+    pub(crate) fn view(&self) -> View {
+        self.viewport
+            .as_ref()
+            .map_or(View::IDENTITY, Viewport::view)
+    }
+}
@@ -0,0 +1,272 @@
+use std::sync::Arc;
+
+use wgpu::{Surface, SurfaceConfiguration, Texture};
+use winit::{dpi::LogicalSize, event_loop::ActiveEventLoop, window::Window};
+
+use crate::{
+    error::GammaError,
+    gamma::{DrawFn, Gamma},
+    rendering::lighting::LightingState,
+};
+
+/// Identifies one of the game's windows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct WindowId(u32);
+
+impl WindowId {
+    /// The window opened by `GammaBuilder::run`.
+    pub const MAIN: WindowId = WindowId(0);
+}
+
+/// A window opened with `open_window`, drawn by its own draw function after the main window.
+pub(crate) struct ExtraWindow<S> {
+    pub id: WindowId,
+    pub window: Arc<Window>,
+    pub surface: Surface<'static>,
+    pub config: SurfaceConfiguration,
+    pub draw_fn: DrawFn<S>,
+    /// Swapped in while the window is drawn so each window keeps buffers of its own size.
+    pub stencil_texture: Option<Texture>,
+    pub msaa_texture: Option<Texture>,
+    pub lighting: LightingState,
+}
+
+/// A window waiting for the event loop to create it.
+pub(crate) struct WindowRequest<S> {
+    id: WindowId,
+    title: String,
+    size: LogicalSize<f64>,
+    draw_fn: DrawFn<S>,
+}
+
+impl<S> Gamma<S> {
+    /// Open another window that shows the same game state, drawn with its own draw function.
+    ///
+    /// The window is created before the next frame. Each frame the main window is drawn first,
+    /// then every extra window in the order they were opened. Keyboard input from all windows
+    /// is combined. Nothing is opened when running headless. If the window cannot be created,
+    /// the error is available from `take_window_error`.
+    pub fn open_window(
+        &mut self,
+        title: impl Into<String>,
+        width: u16,
+        height: u16,
+        draw: DrawFn<S>,
+    ) -> WindowId {
+        self.last_window_id += 1;
+        let id = WindowId(self.last_window_id);
+
+        self.window_requests.push(WindowRequest {
+            id,
+            title: title.into(),
+            size: LogicalSize::new(width.into(), height.into()),
+            draw_fn: draw,
+        });
+
+        id
+    }
+
+    /// Close a window opened with `open_window` at the end of the frame.
+    /// Closing the main window ends the game.
+    pub fn close_window(&mut self, id: WindowId) {
+        self.window_requests.retain(|request| request.id != id);
+
+        if !self.windows_to_close.contains(&id) {
+            self.windows_to_close.push(id);
+        }
+    }
+
+    /// Whether a window is open, or about to be opened at the start of the next frame.
+    pub fn is_window_open(&self, id: WindowId) -> bool {
+        if self.windows_to_close.contains(&id) {
+            return false;
+        }
+
+        if id == WindowId::MAIN {
+            return self.window.is_some();
+        }
+
+        self.extra_windows.iter().any(|window| window.id == id)
+            || self.window_requests.iter().any(|request| request.id == id)
+    }
+
+    /// Why a window requested with `open_window` could not be opened, if it failed.
+    /// Returns `None` while it is still waiting to be opened.
+    pub fn take_window_error(&mut self, id: WindowId) -> Option<GammaError> {
+        self.window_errors.remove(&id)
+    }
+
+    /// The window being drawn right now.
+    pub fn current_window(&self) -> WindowId {
+        match self.drawing_window {
+            Some(index) => self.extra_windows[index].id,
+            None => WindowId::MAIN,
+        }
+    }
+
+    /// The surface frames are drawn to, which belongs to the extra window being drawn, if any.
+    pub(crate) fn target_surface(&self) -> Option<&Surface<'static>> {
+        match self.drawing_window {
+            Some(index) => Some(&self.extra_windows[index].surface),
+            None => self.surface.as_ref(),
+        }
+    }
+
+    /// Create the windows requested with `open_window`.
+    pub(crate) fn open_requested_windows(&mut self, event_loop: &ActiveEventLoop) {
+        for request in std::mem::take(&mut self.window_requests) {
+            match self.create_extra_window(event_loop, &request) {
+                Ok(window) => self.extra_windows.push(window),
+                Err(e) => {
+                    eprintln!("Failed to open window '{}': {}", request.title, e);
+                    self.window_errors.insert(request.id, e);
+                }
+            }
+        }
+    }
+
+    fn create_extra_window(
+        &self,
+        event_loop: &ActiveEventLoop,
+        request: &WindowRequest<S>,
+    ) -> Result<ExtraWindow<S>, GammaError> {
+        let (instance, adapter, device, main_config) = match (
+            self.instance.as_ref(),
+            self.adapter.as_ref(),
+            self.device.as_ref(),
+            self.surface_config.as_ref(),
+        ) {
+            (Some(i), Some(a), Some(d), Some(c)) => (i, a, d, c),
+            _ => return Err(GammaError::NotInitialized("Rendering")),
+        };
+
+        let attributes = Window::default_attributes()
+            .with_title(request.title.clone())
+            .with_inner_size(request.size)
+            .with_resizable(self.resizable)
+            .with_transparent(self.transparent);
+        let window = Arc::new(
+            event_loop
+                .create_window(attributes)
+                .map_err(|e| GammaError::Window(format!("Failed to create the window: {}", e)))?,
+        );
+
+        let surface = instance
+            .create_surface(window.clone())
+            .map_err(|e| GammaError::Surface(format!("Failed to create the surface: {}", e)))?;
+
+        // The pipelines are built for the main window's format, so every window must share it.
+        let capabilities = surface.get_capabilities(adapter);
+        if !capabilities.formats.contains(&main_config.format) {
+            return Err(GammaError::Surface(format!(
+                "The window does not support the {:?} format of the main window",
+                main_config.format
+            )));
+        }
+
+        let size = window.inner_size();
+        let config = SurfaceConfiguration {
+            width: size.width.max(1),
+            height: size.height.max(1),
+            ..main_config.clone()
+        };
+        surface.configure(device, &config);
+
+        Ok(ExtraWindow {
+            id: request.id,
+            window,
+            surface,
+            config,
+            draw_fn: request.draw_fn,
+            stencil_texture: None,
+            msaa_texture: None,
+            lighting: LightingState::default(),
+        })
+    }
+
+    /// Draw and present every extra window, keeping the main window's frame aside meanwhile.
+    pub(crate) fn draw_extra_windows(&mut self, state: &mut S) {
+        if self.extra_windows.is_empty() {
+            return;
+        }
+
+        let main_frame = self.current_frame.take();
+        let main_clip_stack = std::mem::take(&mut self.clip_stack);
+        let (main_writing_mask, main_mask_mode) = (self.writing_mask, self.mask_mode);
+
+        for index in 0..self.extra_windows.len() {
+            self.drawing_window = Some(index);
+            self.swap_window_buffers(index);
+
+            let draw_fn = self.extra_windows[index].draw_fn;
+            draw_fn(self, state);
+            self.finish_frame();
+
+            self.swap_window_buffers(index);
+        }
+
+        self.drawing_window = None;
+        self.current_frame = main_frame;
+        self.clip_stack = main_clip_stack;
+        self.writing_mask = main_writing_mask;
+        self.mask_mode = main_mask_mode;
+    }
+
+    fn swap_window_buffers(&mut self, index: usize) {
+        let window = &mut self.extra_windows[index];
+        std::mem::swap(&mut self.stencil_texture, &mut window.stencil_texture);
+        std::mem::swap(&mut self.msaa_texture, &mut window.msaa_texture);
+        std::mem::swap(&mut self.lighting, &mut window.lighting);
+    }
+
+    /// Close the windows passed to `close_window`. Returns true when the main window was closed.
+    pub(crate) fn close_requested_windows(&mut self) -> bool {
+        let mut close_main = false;
+
+        for id in std::mem::take(&mut self.windows_to_close) {
+            if id == WindowId::MAIN {
+                close_main = true;
+            }
+            self.extra_windows.retain(|window| window.id != id);
+        }
+
+        close_main
+    }
+
+    /// Close an extra window the user closed.
+    pub(crate) fn close_extra_window(&mut self, id: winit::window::WindowId) {
+        self.extra_windows.retain(|window| window.window.id() != id);
+    }
+
+    /// Match the surface of any window to its new size.
+    pub(crate) fn resize_window(&mut self, id: winit::window::WindowId, width: u32, height: u32) {
+        let Some(device) = self.device.as_ref() else {
+            return;
+        };
+        if width == 0 || height == 0 {
+            // Minimized windows keep their old surface until they are restored.
+            return;
+        }
+
+        if self.window.as_ref().is_some_and(|window| window.id() == id) {
+            if let (Some(surface), Some(config)) =
+                (self.surface.as_ref(), self.surface_config.as_mut())
+            {
+                config.width = width;
+                config.height = height;
+                surface.configure(device, config);
+            }
+            return;
+        }
+
+        if let Some(window) = self
+            .extra_windows
+            .iter_mut()
+            .find(|window| window.window.id() == id)
+        {
+            window.config.width = width;
+            window.config.height = height;
+            window.surface.configure(device, &window.config);
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use gamma::prelude::*;
+
+const WIDTH: u16 = 64;
+const HEIGHT: u16 = 48;
+
+struct Scene {
+    square: Texture,
+}
+
+fn init(gamma: &mut Gamma<Scene>) -> Scene {
+    let pixels: Vec<u8> = [0, 0, 255, 255].repeat(16 * 16);
+    let square = gamma.create_texture_from_rgba(16, 16, &pixels).unwrap();
+    Scene { square }
+}
+
+fn draw(gamma: &mut Gamma<Scene>, scene: &mut Scene) {
+    gamma.clear_screen(Color::RED);
+    gamma.draw_texture(&scene.square, 8.0, 4.0, Flip::None);
+}
+
+/// Render the scene, or `None` when this machine has no graphics adapter to render with.
+fn render(frames: u32) -> Option<RgbaImage> {
+    let result = GammaBuilder::default()
+        .with_size(WIDTH, HEIGHT)
+        .on_init(init)
+        .on_draw(draw)
+        .run_headless(frames);
+
+    match result {
+        Ok(image) => Some(image),
+        Err(GammaError::Adapter(message)) => {
+            eprintln!("Skipping headless test, no graphics adapter: {}", message);
+            None
+        }
+        Err(e) => panic!("Headless rendering failed: {}", e),
+    }
+}
+
+#[test]
+fn draws_texture_over_cleared_frame() {
+    let Some(image) = render(1) else {
+        return;
+    };
+
+    let (width, height) = (WIDTH as u32, HEIGHT as u32);
+    assert_eq!(image.dimensions(), (width, height));
+
+    let red = [255, 0, 0, 255];
+    let blue = [0, 0, 255, 255];
+    for (x, y, expected) in [
+        (0, 0, red),
+        (width - 1, height - 1, red),
+        (7, 4, red),
+        (8, 4, blue),
+        (15, 11, blue),
+        (23, 19, blue),
+        (24, 19, red),
+        (23, 20, red),
+    ] {
+        assert_eq!(image.get_pixel(x, y).0, expected, "pixel at ({}, {})", x, y);
+    }
+}